@@ -0,0 +1,173 @@
+use crate::lazy::{LazyMessage, LazyOptions};
+use crate::protocol::{DefinitionMessage, FitDefinitionMessage, FitMessage, FitMessageHeader};
+use crate::Fit;
+use binrw::{BinReaderExt, BinResult};
+use rayon::prelude::*;
+use std::fs::read;
+use std::io::Cursor;
+use std::path::Path;
+
+/// Data messages decoded by one task of `Fit::read_par`.
+const MESSAGES_PER_TASK: usize = 1024;
+
+/// Totals over the files read by `Fit::read_files` or `Fit::map_files`.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct BatchStats {
+    pub files: usize,
+    /// Files that could not be read or decoded.
+    pub failed: usize,
+    pub bytes: u64,
+    /// Data messages decoded.
+    pub messages: usize,
+}
+
+impl BatchStats {
+    fn add(&mut self, other: &BatchStats) {
+        self.files += other.files;
+        self.failed += other.failed;
+        self.bytes += other.bytes;
+        self.messages += other.messages;
+    }
+}
+
+impl Fit {
+    /// Reads and decodes files on the rayon thread pool, giving each result in the order of
+    /// `paths`.
+    ///
+    /// Files are decoded as by `Fit::read_par`: developer fields are dropped from the data
+    /// messages rather than failing the file as `Fit::read` does.
+    pub fn read_files<P: AsRef<Path> + Sync>(paths: &[P]) -> (Vec<BinResult<Fit>>, BatchStats) {
+        Fit::map_files(paths, |_, fit| fit)
+    }
+
+    /// Like `Fit::read_files`, but hands each result to `f` as soon as the file is decoded, so
+    /// only what `f` returns is kept in memory.
+    pub fn map_files<P, T, F>(paths: &[P], f: F) -> (Vec<T>, BatchStats)
+    where
+        P: AsRef<Path> + Sync,
+        T: Send,
+        F: Fn(&Path, BinResult<Fit>) -> T + Sync,
+    {
+        let results: Vec<(T, BatchStats)> = paths
+            .par_iter()
+            .map(|path| {
+                let path = path.as_ref();
+                let mut stats = BatchStats {
+                    files: 1,
+                    ..BatchStats::default()
+                };
+                let fit = read(path).map_err(Into::into).and_then(|buf| {
+                    stats.bytes = buf.len() as u64;
+                    Fit::read_scanned(&buf, MESSAGES_PER_TASK)
+                });
+                match &fit {
+                    Ok(fit) => stats.messages = fit.data_messages().count(),
+                    Err(_) => stats.failed = 1,
+                }
+                (f(path, fit), stats)
+            })
+            .collect();
+        let mut total = BatchStats::default();
+        let values = results
+            .into_iter()
+            .map(|(value, stats)| {
+                total.add(&stats);
+                value
+            })
+            .collect();
+        (values, total)
+    }
+
+    /// Decodes one large file on the rayon thread pool.
+    ///
+    /// A first pass finds the message boundaries and which definition each data message
+    /// follows, as `Fit::read_lazy` does. With the definitions resolved the data messages no
+    /// longer depend on the ones before them, so they are split into runs decoded in parallel.
+    /// The result is the same as `Fit::read`, except that developer fields are skipped
+    /// rather than rejected.
+    pub fn read_par(buf: &[u8]) -> BinResult<Fit> {
+        Fit::read_scanned(buf, MESSAGES_PER_TASK)
+    }
+
+    fn read_scanned(buf: &[u8], messages_per_task: usize) -> BinResult<Fit> {
+        let lazy = Fit::read_lazy(buf, &LazyOptions::default())?;
+        let decoded: Vec<_> = lazy
+            .messages
+            .par_iter()
+            .with_min_len(messages_per_task)
+            .map(LazyMessage::decode)
+            .collect();
+
+        let mut data = Vec::with_capacity(lazy.definitions.len() + decoded.len());
+        let mut definitions = lazy.definitions.iter().peekable();
+        for (msg, decoded) in lazy.messages.iter().zip(decoded) {
+            while let Some(offset) = definitions.next_if(|offset| **offset < msg.offset) {
+                data.push(FitMessage::Definition(read_definition(buf, *offset)?));
+            }
+            data.push(FitMessage::Data(decoded));
+        }
+        for offset in definitions {
+            data.push(FitMessage::Definition(read_definition(buf, *offset)?));
+        }
+        Ok(Fit {
+            header: lazy.header,
+            data,
+        })
+    }
+}
+
+fn read_definition(buf: &[u8], offset: usize) -> BinResult<FitDefinitionMessage> {
+    let mut cursor = Cursor::new(&buf[offset..]);
+    let header: FitMessageHeader = cursor.read_ne()?;
+    let data: DefinitionMessage = cursor.read_ne_args((header.dev_fields,))?;
+    Ok(FitDefinitionMessage { header, data })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::data_field::DataField;
+    use crate::protocol::message_type::MessageType;
+    use crate::protocol::value::Value;
+    use crate::protocol::FitMessage;
+    use crate::test_util::{heart_rate, push_records, recording};
+    use crate::Fit;
+    use std::fs::{remove_file, write};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    #[test]
+    fn batch_test() {
+        let mut builder = recording(0..5, heart_rate);
+        builder.push(MessageType::Lap, vec![DataField::new(254, Value::U16(0))]);
+        push_records(&mut builder, 5..10, heart_rate);
+        let buf = builder.build().to_bytes().unwrap();
+        let expected = Fit::read(buf.clone()).unwrap();
+        // Runs of two messages, so that definitions fall inside and between runs.
+        let fit = Fit::read_scanned(&buf, 2).unwrap();
+        assert_eq!(fit.data.len(), expected.data.len());
+        for (left, right) in fit.data.iter().zip(&expected.data) {
+            match (left, right) {
+                (FitMessage::Definition(left), FitMessage::Definition(right)) => {
+                    assert_eq!(left, right)
+                }
+                (FitMessage::Data(left), FitMessage::Data(right)) => assert_eq!(left, right),
+                _ => panic!("messages out of order"),
+            }
+        }
+
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let stem = format!("fit-rust-batch-test-{}-{}", std::process::id(), nanos);
+        let dir = std::env::temp_dir();
+        let good = dir.join(format!("{}.fit", stem));
+        write(&good, &buf).unwrap();
+        let missing = dir.join(format!("{}-missing.fit", stem));
+        let (results, stats) = Fit::read_files(&[&good, &missing, &good]);
+        assert!(results[0].is_ok() && results[1].is_err() && results[2].is_ok());
+        assert_eq!((stats.files, stats.failed), (3, 1));
+        assert_eq!(stats.bytes, 2 * buf.len() as u64);
+        assert_eq!(stats.messages, 2 * 12);
+        remove_file(good).unwrap();
+    }
+}
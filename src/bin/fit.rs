@@ -0,0 +1,533 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use fit_rust::convert::gpx::TrackSplit;
+use fit_rust::convert::{format_time, parse_time};
+use fit_rust::device::garmin_product_name;
+use fit_rust::edit::CropBound;
+use fit_rust::protocol::data_field::DataField;
+use fit_rust::protocol::get_field_name::get_field_name;
+use fit_rust::protocol::macros::get_field_scaled;
+use fit_rust::protocol::message_type::MessageType;
+use fit_rust::protocol::value::Value;
+use fit_rust::protocol::{FitDataMessage, FitMessage};
+use fit_rust::validate::Severity;
+use fit_rust::Fit;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "fit", version, about = "Inspect, convert and edit FIT files")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the header, file type, device and duration of a file
+    Info { file: PathBuf },
+    /// Print every message with its field names
+    Dump {
+        file: PathBuf,
+        /// Also print the definition messages
+        #[arg(long)]
+        definitions: bool,
+    },
+    /// Print the bytes of a file next to what they decode to
+    Inspect { file: PathBuf },
+    /// Merge activities recorded one after the other into one file
+    Merge {
+        #[arg(required = true, num_args = 2..)]
+        files: Vec<PathBuf>,
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Convert a file to JSON, to CSV (records only) or to GPX
+    Convert {
+        file: PathBuf,
+        #[arg(long, value_enum)]
+        to: Format,
+        /// Written to standard output if not given
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Keep the part of an activity between two points
+    ///
+    /// Points are seconds after the first record (`600`), distances (`1200m`, `5km`) or
+    /// ISO 8601 times (`2023-09-29T05:49:21Z`).
+    Crop {
+        file: PathBuf,
+        #[arg(long, value_parser = parse_point)]
+        start: Option<Point>,
+        #[arg(long, value_parser = parse_point)]
+        end: Option<Point>,
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Check a file against the FIT file rules, failing if it has errors
+    Validate { file: PathBuf },
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum Format {
+    Json,
+    Csv,
+    Gpx,
+}
+
+#[derive(Copy, Clone)]
+enum Point {
+    Elapsed(u32),
+    Time(u32),
+    Distance(f64),
+}
+
+fn parse_point(s: &str) -> Result<Point, String> {
+    let distance =
+        |s: &str, factor: f64| s.parse::<f64>().ok().map(|d| Point::Distance(d * factor));
+    let point = if let Some(km) = s.strip_suffix("km") {
+        distance(km, 1000.0)
+    } else if let Some(m) = s.strip_suffix('m') {
+        distance(m, 1.0)
+    } else if s.contains('T') {
+        parse_time(s).map(Point::Time)
+    } else {
+        s.parse().ok().map(Point::Elapsed)
+    };
+    point.ok_or_else(|| format!("expected seconds, a distance or a time, got {}", s))
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse().command) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn read_bytes(path: &Path) -> Result<Vec<u8>, Box<dyn Error>> {
+    Ok(fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?)
+}
+
+fn read(path: &Path) -> Result<Fit, Box<dyn Error>> {
+    Ok(Fit::read(read_bytes(path)?)?)
+}
+
+fn output(path: Option<&Path>, contents: &str) -> Result<(), Box<dyn Error>> {
+    match path {
+        Some(path) => fs::write(path, contents)?,
+        None => print!("{}", contents),
+    }
+    Ok(())
+}
+
+/// Runs a command, returning whether the file passed it.
+fn run(command: Command) -> Result<bool, Box<dyn Error>> {
+    match command {
+        Command::Info { file } => print!("{}", info(&read(&file)?)),
+        Command::Dump { file, definitions } => print!("{}", dump(&read(&file)?, definitions)),
+        Command::Inspect { file } => print!("{}", Fit::inspect(&read_bytes(&file)?)),
+        Command::Merge { files, output } => Fit::merge(files, output)?,
+        Command::Convert {
+            file,
+            to,
+            output: path,
+        } => {
+            let fit = read(&file)?;
+            let contents = match to {
+                Format::Json => to_json(&fit),
+                Format::Csv => to_csv(&fit),
+                Format::Gpx => fit.to_gpx(TrackSplit::Session),
+            };
+            output(path.as_deref(), &contents)?;
+        }
+        Command::Crop {
+            file,
+            start,
+            end,
+            output,
+        } => {
+            let mut fit = read(&file)?;
+            let first = records(&fit)
+                .find_map(|record| record.timestamp())
+                .unwrap_or(0);
+            let bound = |point: Option<Point>, open: u32| match point {
+                None => CropBound::Time(open),
+                Some(Point::Elapsed(seconds)) => CropBound::Time(first.saturating_add(seconds)),
+                Some(Point::Time(t)) => CropBound::Time(t),
+                Some(Point::Distance(d)) => CropBound::Distance(d),
+            };
+            fit.crop(bound(start, 0), bound(end, u32::MAX))?;
+            fit.write(output)?;
+        }
+        Command::Validate { file } => {
+            let issues = read(&file)?.validate();
+            for issue in &issues {
+                println!("{}", issue);
+            }
+            let errors = issues
+                .iter()
+                .filter(|issue| issue.severity == Severity::Error)
+                .count();
+            println!("{} errors, {} warnings", errors, issues.len() - errors);
+            return Ok(errors == 0);
+        }
+    }
+    Ok(true)
+}
+
+fn records(fit: &Fit) -> impl Iterator<Item = &FitDataMessage> {
+    fit.messages(MessageType::Record)
+}
+
+fn name(message_type: MessageType, field_num: u8) -> String {
+    get_field_name(message_type, field_num)
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("field_{}", field_num))
+}
+
+fn is_coordinate(message_type: MessageType, field_num: u8) -> bool {
+    get_field_name(message_type, field_num)
+        .is_some_and(|name| name.ends_with("_lat") || name.ends_with("_long"))
+}
+
+enum Shown {
+    Number(f64),
+    Text(String),
+    Invalid,
+}
+
+/// The physical value of a field: scaled numbers, enum names and ISO 8601 times.
+fn show(message_type: MessageType, field: &DataField) -> Shown {
+    match &field.value {
+        Value::None => Shown::Invalid,
+        Value::Enum(name) => Shown::Text(name.to_string()),
+        Value::String(s) => Shown::Text(s.clone()),
+        Value::Time(t) => Shown::Text(format_time(*t)),
+        Value::ArrU8(v) => Shown::Text(format!("{:?}", v)),
+        Value::ArrU16(v) => Shown::Text(format!("{:?}", v)),
+        Value::ArrU32(v) => Shown::Text(format!("{:?}", v)),
+        // Unset coordinates decode to 180°.
+        Value::F32(v) if v.abs() >= 180.0 && is_coordinate(message_type, field.field_num) => {
+            Shown::Invalid
+        }
+        // Printed with f32 precision rather than as the f64 nearest to it.
+        Value::F32(v) => Shown::Number(v.to_string().parse().unwrap_or(*v as f64)),
+        _ => match get_field_scaled(message_type, field.field_num, std::slice::from_ref(field)) {
+            Some(v) => Shown::Number(v),
+            None => Shown::Invalid,
+        },
+    }
+}
+
+fn info(fit: &Fit) -> String {
+    let header = &fit.header;
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "protocol:  {}.{}",
+        header.protocol_version >> 4,
+        header.protocol_version & 0x0F
+    );
+    let _ = writeln!(
+        out,
+        "profile:   {}.{:02}",
+        header.profile_version / 100,
+        header.profile_version % 100
+    );
+    let _ = writeln!(out, "data size: {} bytes", header.data_size);
+
+    if let Some(file_id) = fit.first(MessageType::FileId) {
+        let values = &file_id.data.values;
+        let shown = |field_num| {
+            values
+                .iter()
+                .find(|field| field.field_num == field_num)
+                .map(|field| show(MessageType::FileId, field))
+        };
+        if let Some(Shown::Text(file_type)) = shown(0) {
+            let _ = writeln!(out, "type:      {}", file_type);
+        }
+        let mut device = match shown(1) {
+            Some(Shown::Text(manufacturer)) => manufacturer,
+            Some(Shown::Number(manufacturer)) => format!("manufacturer {}", manufacturer),
+            _ => "unknown".to_string(),
+        };
+        if let Some(Shown::Number(product)) = shown(2) {
+            let product = product as u16;
+            match garmin_product_name(product).filter(|_| device == "garmin") {
+                Some(name) => {
+                    let _ = write!(device, " {} ({})", name, product);
+                }
+                None => {
+                    let _ = write!(device, " product {}", product);
+                }
+            }
+        }
+        if let Some(Shown::Number(serial)) = shown(3) {
+            let _ = write!(device, ", serial {}", serial);
+        }
+        let _ = writeln!(out, "device:    {}", device);
+        if let Some(Shown::Text(created)) = shown(4) {
+            let _ = writeln!(out, "created:   {}", created);
+        }
+    }
+
+    let times: Vec<u32> = records(fit)
+        .filter_map(|record| record.timestamp())
+        .collect();
+    // The total elapsed time of the sessions, or `None` when no session has one.
+    let elapsed = fit
+        .messages(MessageType::Session)
+        .filter_map(|session| session.get_f64(7))
+        .fold(None, |total: Option<f64>, elapsed| {
+            Some(total.unwrap_or_default() + elapsed)
+        });
+    let duration = elapsed.or_else(|| match (times.iter().min(), times.iter().max()) {
+        (Some(first), Some(last)) => Some((last - first) as f64),
+        _ => None,
+    });
+    if let Some(duration) = duration {
+        let seconds = duration.round() as u64;
+        let _ = writeln!(
+            out,
+            "duration:  {}:{:02}:{:02}",
+            seconds / 3600,
+            seconds % 3600 / 60,
+            seconds % 60
+        );
+    }
+
+    let mut counts: Vec<(MessageType, usize)> = vec![];
+    for message_type in fit.data_messages().map(|msg| msg.data.message_type) {
+        match counts.iter_mut().find(|(t, _)| *t == message_type) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((message_type, 1)),
+        }
+    }
+    let _ = writeln!(out, "messages:");
+    for (message_type, count) in counts {
+        let _ = writeln!(out, "  {:?}: {}", message_type, count);
+    }
+    out
+}
+
+fn dump(fit: &Fit, definitions: bool) -> String {
+    let mut out = String::new();
+    for (index, message) in fit.data.iter().enumerate() {
+        match message {
+            FitMessage::Definition(def) if definitions => {
+                let _ = writeln!(
+                    out,
+                    "#{} definition local {} global {}",
+                    index, def.header.local_num, def.data.global_message_number
+                );
+                for field in &def.data.fields {
+                    let _ = writeln!(
+                        out,
+                        "    field {} size {} base type {:#04x}",
+                        field.definition_number, field.size, field.base_type.val
+                    );
+                }
+            }
+            FitMessage::Definition(_) => {}
+            FitMessage::Data(msg) => {
+                let message_type = msg.data.message_type;
+                let _ = writeln!(out, "#{} {:?}", index, message_type);
+                for field in &msg.data.values {
+                    let value = match show(message_type, field) {
+                        Shown::Number(v) => v.to_string(),
+                        Shown::Text(s) => s,
+                        Shown::Invalid => "-".to_string(),
+                    };
+                    let _ = writeln!(
+                        out,
+                        "    {}: {}",
+                        name(message_type, field.field_num),
+                        value
+                    );
+                }
+            }
+        }
+    }
+    out
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// The data messages as a JSON array of `{"message": .., "fields": {..}}` objects.
+fn to_json(fit: &Fit) -> String {
+    let mut out = String::from("[\n");
+    for (i, msg) in fit.data_messages().enumerate() {
+        let (message_type, values) = (msg.data.message_type, &msg.data.values);
+        if i > 0 {
+            out.push_str(",\n");
+        }
+        let _ = write!(
+            out,
+            "  {{\"message\": {}, \"fields\": {{",
+            json_string(&format!("{:?}", message_type))
+        );
+        for (j, field) in values.iter().enumerate() {
+            let value = match show(message_type, field) {
+                Shown::Number(v) if v.is_finite() => v.to_string(),
+                Shown::Text(s) => json_string(&s),
+                _ => "null".to_string(),
+            };
+            let separator = if j > 0 { ", " } else { "" };
+            let _ = write!(
+                out,
+                "{}{}: {}",
+                separator,
+                json_string(&name(message_type, field.field_num)),
+                value
+            );
+        }
+        out.push_str("}}");
+    }
+    out.push_str("\n]\n");
+    out
+}
+
+fn csv_cell(s: String) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s
+    }
+}
+
+/// The records as CSV, with a column for every field any record has.
+fn to_csv(fit: &Fit) -> String {
+    let mut columns: Vec<u8> = vec![];
+    for record in records(fit) {
+        for field in &record.data.values {
+            if !columns.contains(&field.field_num) {
+                columns.push(field.field_num);
+            }
+        }
+    }
+    let mut out = columns
+        .iter()
+        .map(|field_num| name(MessageType::Record, *field_num))
+        .collect::<Vec<_>>()
+        .join(",");
+    out.push('\n');
+    for record in records(fit) {
+        let values = &record.data.values;
+        let row: Vec<String> = columns
+            .iter()
+            .map(|field_num| {
+                let field = values.iter().find(|field| field.field_num == *field_num);
+                match field.map(|field| show(MessageType::Record, field)) {
+                    Some(Shown::Number(v)) => v.to_string(),
+                    Some(Shown::Text(s)) => csv_cell(s),
+                    _ => String::new(),
+                }
+            })
+            .collect();
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{csv_cell, dump, info, json_string};
+    use fit_rust::builder::{scaled_field, FitBuilder};
+    use fit_rust::protocol::data_field::DataField;
+    use fit_rust::protocol::message_type::MessageType;
+    use fit_rust::protocol::value::Value;
+    use fit_rust::Fit;
+
+    /// Three records written out of time order, and optionally a one-hour session.
+    fn activity(session: bool) -> Fit {
+        let start = 1_000_000_000;
+        let mut builder = FitBuilder::new();
+        builder.push(
+            MessageType::FileId,
+            vec![
+                DataField::new(0, Value::Enum("activity")),
+                DataField::new(1, Value::Enum("garmin")),
+            ],
+        );
+        for t in [5, 0, 10] {
+            builder.push(
+                MessageType::Record,
+                vec![
+                    DataField::new(253, Value::Time(start + t)),
+                    DataField::new(3, Value::U8(140)),
+                ],
+            );
+        }
+        if session {
+            builder.push(
+                MessageType::Session,
+                vec![
+                    DataField::new(253, Value::Time(start + 10)),
+                    scaled_field(MessageType::Session, 7, 3600.0),
+                ],
+            );
+        }
+        Fit::read(builder.build().to_bytes().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn info_test() {
+        let out = info(&activity(true));
+        assert!(out.contains("type:      activity\n"));
+        assert!(out.contains("device:    garmin\n"));
+        assert!(out.contains("duration:  1:00:00\n"));
+        assert!(out.contains("  Record: 3\n"));
+        assert!(out.contains("  Session: 1\n"));
+
+        // Without a session the duration spans the earliest to the latest record.
+        let out = info(&activity(false));
+        assert!(out.contains("duration:  0:00:10\n"));
+        assert!(!out.contains("Session"));
+    }
+
+    #[test]
+    fn dump_test() {
+        let fit = activity(false);
+        let out = dump(&fit, false);
+        assert!(out.contains(" Record\n    timestamp: 2001-09-09T01:46:45Z\n    heart_rate: 140\n"));
+        assert!(!out.contains("definition"));
+        let out = dump(&fit, true);
+        assert!(out.contains("definition local 0 global 0\n"));
+    }
+
+    #[test]
+    fn json_string_test() {
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(json_string("a\"b\\c\n"), "\"a\\\"b\\\\c\\u000a\"");
+    }
+
+    #[test]
+    fn csv_cell_test() {
+        assert_eq!(csv_cell("plain".to_string()), "plain");
+        assert_eq!(csv_cell("a,b".to_string()), "\"a,b\"");
+        assert_eq!(csv_cell("say \"hi\"".to_string()), "\"say \"\"hi\"\"\"");
+    }
+}
@@ -0,0 +1,204 @@
+use crate::protocol::base_type::{
+    base_type_size, profile_base_type, FLOAT32, FLOAT64, SINT16, SINT32, SINT64, SINT8, STRING,
+    UINT16, UINT32, UINT64, UINT8,
+};
+use crate::protocol::data_field::DataField;
+use crate::protocol::get_field_offset::get_field_offset_fn;
+use crate::protocol::get_field_scale::get_field_scale_fn;
+use crate::protocol::get_field_string_value::FieldType;
+use crate::protocol::get_field_type::get_field_type_fn;
+use crate::protocol::message_type::MessageType;
+use crate::protocol::value::Value;
+use crate::protocol::{
+    DataMessage, DefinitionMessage, FieldDefinition, FitDataMessage, FitDefinitionMessage,
+    FitHeader, FitMessage, FitMessageHeader,
+};
+use crate::Fit;
+
+const LOCAL_MESSAGE_COUNT: usize = 16;
+
+/// Assembles a `Fit` from data messages, emitting a definition message whenever a message
+/// layout has not been defined yet and recycling the 16 local message numbers round robin.
+#[derive(Debug, Default)]
+pub struct FitBuilder {
+    data: Vec<FitMessage>,
+    locals: Vec<(MessageType, Vec<FieldDefinition>)>,
+    next_local: usize,
+}
+
+impl FitBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a data message. Field base types follow the SDK profile where the value allows
+    /// it, `Value::None` fields are dropped and strings are NUL terminated.
+    pub fn push(&mut self, message_type: MessageType, values: Vec<DataField>) {
+        let mut fields = Vec::with_capacity(values.len());
+        let mut data = Vec::with_capacity(values.len());
+        for mut field in values {
+            if let Some(definition) = field_definition(message_type, &mut field) {
+                fields.push(definition);
+                data.push(field);
+            }
+        }
+        self.push_defined(message_type, fields, data);
+    }
+
+    /// Appends a data message laid out by the given field definitions, which must line up
+    /// with `values`.
+    pub(crate) fn push_defined(
+        &mut self,
+        message_type: MessageType,
+        fields: Vec<FieldDefinition>,
+        values: Vec<DataField>,
+    ) {
+        let local_num = match self
+            .locals
+            .iter()
+            .position(|(t, f)| *t == message_type && *f == fields)
+        {
+            Some(local_num) => local_num,
+            None => self.define(message_type, fields),
+        };
+        self.data.push(FitMessage::Data(FitDataMessage {
+            header: FitMessageHeader::new(false, local_num as u8),
+            data: DataMessage {
+                message_type,
+                values,
+            },
+        }));
+    }
+
+    /// Appends a `timer` event such as `"start"` or `"stop_all"`.
+    pub(crate) fn push_timer_event(&mut self, timestamp: u32, event_type: &'static str) {
+        self.push(
+            MessageType::Event,
+            vec![
+                DataField::new(253, Value::Time(timestamp)),
+                DataField::new(0, Value::Enum("timer")),
+                DataField::new(1, Value::Enum(event_type)),
+                DataField::new(4, Value::U8(0)),
+            ],
+        );
+    }
+
+    fn define(&mut self, message_type: MessageType, fields: Vec<FieldDefinition>) -> usize {
+        let local_num = self.next_local;
+        self.next_local = (self.next_local + 1) % LOCAL_MESSAGE_COUNT;
+        self.data.push(FitMessage::Definition(FitDefinitionMessage {
+            header: FitMessageHeader::new(true, local_num as u8),
+            data: DefinitionMessage::new(false, fields.len() as u8, fields.clone(), message_type),
+        }));
+        if local_num < self.locals.len() {
+            self.locals[local_num] = (message_type, fields);
+        } else {
+            self.locals.push((message_type, fields));
+        }
+        local_num
+    }
+
+    /// Finishes the file with a fresh 14 byte header; sizes and CRCs are filled in on write.
+    pub fn build(self) -> Fit {
+        Fit {
+            header: FitHeader {
+                header_size: 14,
+                protocol_version: 0x20,
+                profile_version: 2132,
+                data_size: 0,
+                data_type: ".FIT".to_string(),
+                crc: Some(0),
+            },
+            data: self.data,
+        }
+    }
+}
+
+/// The definition `FitBuilder::push` gives a field, or `None` for a `Value::None` field.
+/// Strings are NUL terminated on the way.
+pub(crate) fn field_definition(
+    message_type: MessageType,
+    field: &mut DataField,
+) -> Option<FieldDefinition> {
+    let field_type = get_field_type_fn(message_type)(field.field_num as usize);
+    let base_type = field_base_type(field_type, &mut field.value)?;
+    let size = match &field.value {
+        Value::String(s) => s.len().min(u8::MAX as usize) as u8,
+        Value::ArrU8(v) => v.len() as u8,
+        Value::ArrU16(v) => (v.len() * 2) as u8,
+        Value::ArrU32(v) => (v.len() * 4) as u8,
+        _ => base_type_size(base_type),
+    };
+    Some(FieldDefinition::new(
+        field.field_num,
+        size,
+        base_type & 0x80 == 0x80,
+        base_type & 0x1F,
+    ))
+}
+
+fn field_base_type(field_type: FieldType, value: &mut Value) -> Option<u8> {
+    let profile = profile_base_type(field_type);
+    let base_type = match value {
+        Value::None => return None,
+        Value::U8(_) if profile.map(base_type_size) == Some(1) => profile?,
+        Value::U8(_) => UINT8,
+        Value::I8(_) => SINT8,
+        Value::U16(_) if profile.map(base_type_size) == Some(2) => profile?,
+        Value::U16(_) => UINT16,
+        Value::I16(_) => SINT16,
+        Value::U32(_) if profile.map(base_type_size) == Some(4) => profile?,
+        Value::U32(_) | Value::Time(_) => UINT32,
+        Value::I32(_) => SINT32,
+        Value::U64(_) => UINT64,
+        Value::I64(_) => SINT64,
+        Value::F32(_) if field_type == FieldType::Coordinates => SINT32,
+        Value::F32(_) => FLOAT32,
+        Value::F64(_) => FLOAT64,
+        Value::Enum(_) => profile?,
+        Value::String(s) => {
+            if s.len() >= u8::MAX as usize {
+                let mut end = u8::MAX as usize - 1;
+                while !s.is_char_boundary(end) {
+                    end -= 1;
+                }
+                s.truncate(end);
+            }
+            if !s.ends_with('\0') {
+                s.push('\0');
+            }
+            STRING
+        }
+        Value::ArrU8(_) => profile.filter(|t| base_type_size(*t) == 1).unwrap_or(UINT8),
+        Value::ArrU16(_) => profile
+            .filter(|t| base_type_size(*t) == 2)
+            .unwrap_or(UINT16),
+        Value::ArrU32(_) => profile
+            .filter(|t| base_type_size(*t) == 4)
+            .unwrap_or(UINT32),
+    };
+    Some(base_type)
+}
+
+/// Builds a numeric `DataField` from a physical value, applying the profile scale and offset
+/// and using the integer width the profile declares for the field.
+pub fn scaled_field(message_type: MessageType, field_num: u8, value: f64) -> DataField {
+    let mut raw = value;
+    if let Some(offset) = get_field_offset_fn(message_type)(field_num as usize) {
+        raw += offset as f64;
+    }
+    if let Some(scale) = get_field_scale_fn(message_type)(field_num as usize) {
+        raw *= scale as f64;
+    }
+    let raw = raw.round();
+    let value = match get_field_type_fn(message_type)(field_num as usize) {
+        FieldType::Uint8 | FieldType::Uint8Z => Value::U8(raw.clamp(0.0, 254.0) as u8),
+        FieldType::Sint8 => Value::I8(raw.clamp(-127.0, 126.0) as i8),
+        FieldType::Uint16 | FieldType::Uint16Z => Value::U16(raw.clamp(0.0, 65_534.0) as u16),
+        FieldType::Sint16 => Value::I16(raw.clamp(-32_767.0, 32_766.0) as i16),
+        FieldType::Sint32 => Value::I32(raw.clamp(-2_147_483_647.0, 2_147_483_646.0) as i32),
+        FieldType::Float32 => Value::F32(value as f32),
+        _ => Value::U32(raw.clamp(0.0, 4_294_967_294.0) as u32),
+    };
+    DataField::new(field_num, value)
+}
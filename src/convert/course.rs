@@ -0,0 +1,331 @@
+use crate::builder::{scaled_field, FitBuilder};
+use crate::geo::haversine;
+use crate::protocol::data_field::DataField;
+use crate::protocol::get_field_string_value::{
+    get_field_key_from_string, get_field_string_value, FieldType,
+};
+use crate::protocol::io::invalid_data;
+use crate::protocol::message_type::MessageType;
+use crate::protocol::time::parse_time;
+use crate::protocol::value::Value;
+use crate::Fit;
+use binrw::BinResult;
+use roxmltree::{Document, Node};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Settings for `Fit::course_from_gpx`.
+#[derive(Debug, Clone)]
+pub struct CourseOptions {
+    /// Course name, defaults to the GPX track, route or metadata name.
+    pub name: Option<String>,
+    /// A `Sport` profile value such as `"cycling"`.
+    pub sport: &'static str,
+    /// Speed in m/s used to derive timestamps when the GPX points carry none.
+    pub speed: f64,
+    /// Unix time of the first point when the GPX points carry no timestamps, defaults to now.
+    pub start_time: Option<u32>,
+}
+
+impl Default for CourseOptions {
+    fn default() -> Self {
+        Self {
+            name: None,
+            sport: "cycling",
+            speed: 25.0 / 3.6,
+            start_time: None,
+        }
+    }
+}
+
+struct CoursePoint {
+    position: (f64, f64),
+    altitude: Option<f64>,
+    time: Option<u32>,
+    distance: f64,
+}
+
+impl Fit {
+    /// Converts a GPX track or route into a FIT course file: `FileId`, `Course`, timer
+    /// `Event`s, a `Record` per point with the cumulative distance along the track, a `Lap`
+    /// summary and a `CoursePoint` for each waypoint, placed at the nearest track point.
+    pub fn course_from_gpx(xml: &str, options: &CourseOptions) -> BinResult<Fit> {
+        let doc = Document::parse(xml).map_err(|e| invalid_data(e.to_string()))?;
+        let root = doc.root_element();
+        let mut points: Vec<CoursePoint> = root
+            .descendants()
+            .filter(|n| n.has_tag_name("trkpt"))
+            .filter_map(parse_point)
+            .collect();
+        if points.is_empty() {
+            points = root
+                .descendants()
+                .filter(|n| n.has_tag_name("rtept"))
+                .filter_map(parse_point)
+                .collect();
+        }
+        if points.len() < 2 {
+            return Err(invalid_data("GPX document needs at least two track points"));
+        }
+
+        for i in 1..points.len() {
+            points[i].distance =
+                points[i - 1].distance + haversine(points[i - 1].position, points[i].position);
+        }
+        if points.iter().any(|p| p.time.is_none()) {
+            let start = options.start_time.unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs() as u32)
+                    .unwrap_or_default()
+            });
+            let speed = if options.speed > 0.0 {
+                options.speed
+            } else {
+                1.0
+            };
+            for point in &mut points {
+                let offset = (point.distance / speed).round();
+                let time = u32::try_from(offset as u64)
+                    .ok()
+                    .and_then(|offset| start.checked_add(offset))
+                    .ok_or_else(|| invalid_data("course is too long for its speed"))?;
+                point.time = Some(time);
+            }
+        }
+
+        let name = options.name.clone().or_else(|| {
+            ["trk", "rte", "metadata"].iter().find_map(|parent| {
+                let parent = root.children().find(|n| n.has_tag_name(*parent))?;
+                let name = parent.children().find(|n| n.has_tag_name("name"))?;
+                Some(name.text()?.trim().to_string())
+            })
+        });
+
+        let first = &points[0];
+        let last = &points[points.len() - 1];
+        let start_time = first.time.unwrap_or_default();
+        let end_time = last.time.unwrap_or_default();
+
+        let mut builder = FitBuilder::new();
+        builder.push(
+            MessageType::FileId,
+            vec![
+                DataField::new(0, Value::Enum("course")),
+                DataField::new(1, Value::Enum("development")),
+                DataField::new(2, Value::U16(0)),
+                DataField::new(4, Value::Time(start_time)),
+            ],
+        );
+        let mut course = vec![DataField::new(4, Value::Enum(options.sport))];
+        if let Some(name) = name {
+            course.push(DataField::new(5, Value::String(name)));
+        }
+        // processed | valid | time | distance | position
+        course.push(DataField::new(6, Value::U32(0x1F)));
+        builder.push(MessageType::Course, course);
+
+        let (mut ascent, mut descent) = (0.0, 0.0);
+        for pair in points.windows(2) {
+            if let (Some(a), Some(b)) = (pair[0].altitude, pair[1].altitude) {
+                if b > a {
+                    ascent += b - a;
+                } else {
+                    descent += a - b;
+                }
+            }
+        }
+        let elapsed = end_time.saturating_sub(start_time) as f64;
+        builder.push(
+            MessageType::Lap,
+            vec![
+                DataField::new(253, Value::Time(end_time)),
+                DataField::new(0, Value::Enum("lap")),
+                DataField::new(1, Value::Enum("stop")),
+                DataField::new(2, Value::Time(start_time)),
+                DataField::new(3, Value::F32(first.position.0 as f32)),
+                DataField::new(4, Value::F32(first.position.1 as f32)),
+                DataField::new(5, Value::F32(last.position.0 as f32)),
+                DataField::new(6, Value::F32(last.position.1 as f32)),
+                scaled_field(MessageType::Lap, 7, elapsed),
+                scaled_field(MessageType::Lap, 8, elapsed),
+                scaled_field(MessageType::Lap, 9, last.distance),
+                scaled_field(MessageType::Lap, 21, ascent),
+                scaled_field(MessageType::Lap, 22, descent),
+                DataField::new(254, Value::U16(0)),
+            ],
+        );
+
+        builder.push_timer_event(start_time, "start");
+        for point in &points {
+            let mut fields = vec![
+                DataField::new(253, Value::Time(point.time.unwrap_or_default())),
+                DataField::new(0, Value::F32(point.position.0 as f32)),
+                DataField::new(1, Value::F32(point.position.1 as f32)),
+                scaled_field(MessageType::Record, 5, point.distance),
+            ];
+            if let Some(altitude) = point.altitude {
+                fields.push(scaled_field(MessageType::Record, 2, altitude));
+            }
+            builder.push(MessageType::Record, fields);
+        }
+
+        let waypoints = root.children().filter(|n| n.has_tag_name("wpt"));
+        for (index, waypoint) in waypoints.enumerate() {
+            let position = match parse_point(waypoint) {
+                Some(waypoint) => waypoint.position,
+                None => continue,
+            };
+            let nearest = points
+                .iter()
+                .min_by(|a, b| {
+                    haversine(a.position, position).total_cmp(&haversine(b.position, position))
+                })
+                .unwrap_or(first);
+            let mut fields = vec![
+                DataField::new(254, Value::U16(index as u16)),
+                DataField::new(1, Value::Time(nearest.time.unwrap_or_default())),
+                DataField::new(2, Value::F32(position.0 as f32)),
+                DataField::new(3, Value::F32(position.1 as f32)),
+                scaled_field(MessageType::CoursePoint, 4, nearest.distance),
+                DataField::new(5, Value::Enum(course_point_type(waypoint))),
+            ];
+            if let Some(name) = child_text(waypoint, "name") {
+                fields.push(DataField::new(6, Value::String(name.to_string())));
+            }
+            builder.push(MessageType::CoursePoint, fields);
+        }
+        builder.push_timer_event(end_time, "stop_disable_all");
+        Ok(builder.build())
+    }
+}
+
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.children()
+        .find(|n| n.has_tag_name(name))?
+        .text()
+        .map(str::trim)
+}
+
+fn parse_point(node: Node) -> Option<CoursePoint> {
+    let lat: f64 = node.attribute("lat")?.trim().parse().ok()?;
+    let long: f64 = node.attribute("lon")?.trim().parse().ok()?;
+    Some(CoursePoint {
+        position: (lat, long),
+        altitude: child_text(node, "ele").and_then(|e| e.parse().ok()),
+        time: child_text(node, "time").and_then(parse_time),
+        distance: 0.0,
+    })
+}
+
+/// Maps the GPX `<type>` or `<sym>` of a waypoint onto a `CoursePoint` profile value.
+fn course_point_type(waypoint: Node) -> &'static str {
+    ["type", "sym"]
+        .iter()
+        .filter_map(|name| child_text(waypoint, name))
+        .find_map(|s| {
+            let key = s.to_lowercase().replace([' ', '-'], "_");
+            let value = get_field_key_from_string(FieldType::CoursePoint, &key)?;
+            get_field_string_value(FieldType::CoursePoint, value)
+        })
+        .unwrap_or("generic")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::convert::course::CourseOptions;
+    use crate::geo::haversine;
+    use crate::protocol::message_type::MessageType;
+    use crate::protocol::value::Value;
+    use crate::Fit;
+
+    const GPX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="test" xmlns="http://www.topografix.com/GPX/1/1">
+  <wpt lat="30.5011" lon="114.2501">
+    <name>Top</name>
+    <type>Summit</type>
+  </wpt>
+  <trk>
+    <name>Loop</name>
+    <trkseg>
+      <trkpt lat="30.5" lon="114.25"><ele>10</ele></trkpt>
+      <trkpt lat="30.501" lon="114.25"><ele>15</ele></trkpt>
+      <trkpt lat="30.501" lon="114.252"><ele>12</ele></trkpt>
+    </trkseg>
+  </trk>
+</gpx>"#;
+
+    #[test]
+    fn course_from_gpx_test() {
+        let start = 1_000_000_000;
+        let options = CourseOptions {
+            speed: 5.0,
+            start_time: Some(start),
+            ..CourseOptions::default()
+        };
+        let fit = Fit::course_from_gpx(GPX, &options).unwrap();
+        assert_eq!(
+            fit.first(MessageType::Course).unwrap().get(5),
+            Some(&Value::String("Loop\0".to_string()))
+        );
+        let fit = Fit::read(fit.to_bytes().unwrap()).unwrap();
+
+        let points = [(30.5, 114.25), (30.501, 114.25), (30.501, 114.252)];
+        let mut distances = vec![0.0];
+        for pair in points.windows(2) {
+            distances.push(distances[distances.len() - 1] + haversine(pair[0], pair[1]));
+        }
+        let times: Vec<u32> = distances
+            .iter()
+            .map(|distance| start + (distance / 5.0).round() as u32)
+            .collect();
+        let records: Vec<_> = fit.messages(MessageType::Record).collect();
+        assert_eq!(records.len(), 3);
+        for (i, record) in records.iter().enumerate() {
+            assert!((record.get_f64(5).unwrap() - distances[i]).abs() < 0.01);
+            assert_eq!(record.timestamp(), Some(times[i]));
+        }
+
+        let lap = fit.first(MessageType::Lap).unwrap();
+        assert_eq!(lap.get(2), Some(&Value::Time(start)));
+        assert_eq!(lap.get_f64(7), Some((times[2] - start) as f64));
+        assert!((lap.get_f64(9).unwrap() - distances[2]).abs() < 0.01);
+        assert_eq!(lap.get_f64(21), Some(5.0));
+        assert_eq!(lap.get_f64(22), Some(3.0));
+
+        let course_point = fit.first(MessageType::CoursePoint).unwrap();
+        assert_eq!(course_point.get(1), Some(&Value::Time(times[1])));
+        assert!((course_point.get_f64(4).unwrap() - distances[1]).abs() < 0.01);
+        assert_eq!(course_point.get(5), Some(&Value::Enum("summit")));
+        assert_eq!(
+            course_point.get(6),
+            Some(&Value::String("Top\0".to_string()))
+        );
+
+        let route = GPX
+            .replace("<trk>", "<rte>")
+            .replace("</trk>", "</rte>")
+            .replace("<trkseg>", "")
+            .replace("</trkseg>", "")
+            .replace("trkpt", "rtept");
+        let fit = Fit::course_from_gpx(&route, &options).unwrap();
+        assert_eq!(fit.messages(MessageType::Record).count(), 3);
+        assert_eq!(
+            fit.first(MessageType::Course).unwrap().get(5),
+            Some(&Value::String("Loop\0".to_string()))
+        );
+
+        let single = r#"<gpx><trk><trkseg><trkpt lat="30.5" lon="114.25"/></trkseg></trk></gpx>"#;
+        assert!(Fit::course_from_gpx(single, &options).is_err());
+
+        let slow = CourseOptions {
+            speed: 1e-9,
+            ..options.clone()
+        };
+        assert!(Fit::course_from_gpx(GPX, &slow).is_err());
+        let late = CourseOptions {
+            start_time: Some(u32::MAX - 10),
+            ..options
+        };
+        assert!(Fit::course_from_gpx(GPX, &late).is_err());
+    }
+}
@@ -0,0 +1,240 @@
+use crate::convert::xml::escape;
+use crate::protocol::data_field::DataField;
+use crate::protocol::macros::{get_field_scaled, get_field_value, get_position};
+use crate::protocol::message_type::MessageType;
+use crate::protocol::time::format_time;
+use crate::protocol::value::Value;
+use crate::protocol::FitMessage;
+use crate::Fit;
+use binrw::BinResult;
+use std::fmt::Write;
+use std::fs::write;
+use std::path::Path;
+
+const GPX_HEADER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="fit-rust" xmlns="http://www.topografix.com/GPX/1/1" xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v1" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="http://www.topografix.com/GPX/1/1 http://www.topografix.com/GPX/1/1/gpx.xsd http://www.garmin.com/xmlschemas/TrackPointExtension/v1 http://www.garmin.com/xmlschemas/TrackPointExtensionv1.xsd">
+"#;
+
+/// How the records of an activity are grouped into GPX tracks.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TrackSplit {
+    /// One `<trk>` per `Session`, holding a single `<trkseg>`.
+    Session,
+    /// One `<trk>` per `Session`, with a `<trkseg>` per `Lap`.
+    Lap,
+}
+
+impl Fit {
+    /// Converts the `Record` messages into a GPX 1.1 document.
+    ///
+    /// Heart rate, cadence and temperature are written with the Garmin `TrackPointExtension`,
+    /// power as a plain `<power>` extension. Records without a position are skipped.
+    pub fn to_gpx(&self, split: TrackSplit) -> String {
+        let mut sessions: Vec<(u32, Option<&'static str>)> = vec![];
+        let mut laps: Vec<u32> = vec![];
+        for message in &self.data {
+            if let FitMessage::Data(msg) = message {
+                let start = match get_field_value(2, &msg.data.values) {
+                    Some(Value::Time(t)) => t,
+                    _ => continue,
+                };
+                match msg.data.message_type {
+                    MessageType::Session => {
+                        let sport = match get_field_value(5, &msg.data.values) {
+                            Some(Value::Enum(sport)) => Some(sport),
+                            _ => None,
+                        };
+                        sessions.push((start, sport));
+                    }
+                    MessageType::Lap => laps.push(start),
+                    _ => {}
+                }
+            }
+        }
+        sessions.sort_by_key(|(start, _)| *start);
+        laps.sort_unstable();
+
+        let mut gpx = String::from(GPX_HEADER);
+        let mut current: Option<(usize, usize)> = None;
+        for message in &self.data {
+            let msg = match message {
+                FitMessage::Data(msg) if msg.data.message_type == MessageType::Record => msg,
+                _ => continue,
+            };
+            let values = &msg.data.values;
+            let position = get_position(0, 1, values);
+            let timestamp = match get_field_value(253, values) {
+                Some(Value::Time(t)) => t,
+                _ => continue,
+            };
+            let (lat, long) = match position {
+                Some(position) => position,
+                None => continue,
+            };
+            let session = sessions
+                .partition_point(|(start, _)| *start <= timestamp)
+                .saturating_sub(1);
+            let segment = match split {
+                TrackSplit::Session => 0,
+                TrackSplit::Lap => laps.partition_point(|start| *start <= timestamp),
+            };
+            match current {
+                Some((s, seg)) if s == session && seg == segment => {}
+                Some((s, _)) if s == session => {
+                    gpx.push_str("    </trkseg>\n    <trkseg>\n");
+                }
+                _ => {
+                    if current.is_some() {
+                        gpx.push_str("    </trkseg>\n  </trk>\n");
+                    }
+                    gpx.push_str("  <trk>\n");
+                    let _ = writeln!(gpx, "    <name>Session {}</name>", session + 1);
+                    if let Some(Some(sport)) = sessions.get(session).map(|(_, sport)| sport) {
+                        let _ = writeln!(gpx, "    <type>{}</type>", escape(sport));
+                    }
+                    gpx.push_str("    <trkseg>\n");
+                }
+            }
+            current = Some((session, segment));
+            write_trkpt(&mut gpx, lat, long, timestamp, values);
+        }
+        if current.is_some() {
+            gpx.push_str("    </trkseg>\n  </trk>\n");
+        }
+        gpx.push_str("</gpx>\n");
+        gpx
+    }
+
+    pub fn write_gpx<P: AsRef<Path>>(&self, path: P, split: TrackSplit) -> BinResult<()> {
+        write(path, self.to_gpx(split))?;
+        Ok(())
+    }
+}
+
+fn write_trkpt(gpx: &mut String, lat: f64, long: f64, timestamp: u32, values: &[DataField]) {
+    let _ = writeln!(gpx, "      <trkpt lat=\"{:.7}\" lon=\"{:.7}\">", lat, long);
+    let altitude = get_field_scaled(MessageType::Record, 78, values)
+        .or_else(|| get_field_scaled(MessageType::Record, 2, values));
+    if let Some(altitude) = altitude {
+        let _ = writeln!(gpx, "        <ele>{:.1}</ele>", altitude);
+    }
+    let _ = writeln!(gpx, "        <time>{}</time>", format_time(timestamp));
+
+    let temperature = get_field_scaled(MessageType::Record, 13, values);
+    let heart_rate = get_field_scaled(MessageType::Record, 3, values);
+    let cadence = get_field_scaled(MessageType::Record, 4, values);
+    let power = get_field_scaled(MessageType::Record, 7, values);
+    if temperature.is_some() || heart_rate.is_some() || cadence.is_some() || power.is_some() {
+        gpx.push_str("        <extensions>\n");
+        if temperature.is_some() || heart_rate.is_some() || cadence.is_some() {
+            gpx.push_str("          <gpxtpx:TrackPointExtension>\n");
+            if let Some(temperature) = temperature {
+                let _ = writeln!(
+                    gpx,
+                    "            <gpxtpx:atemp>{}</gpxtpx:atemp>",
+                    temperature
+                );
+            }
+            if let Some(heart_rate) = heart_rate {
+                let _ = writeln!(gpx, "            <gpxtpx:hr>{}</gpxtpx:hr>", heart_rate);
+            }
+            if let Some(cadence) = cadence {
+                let _ = writeln!(gpx, "            <gpxtpx:cad>{}</gpxtpx:cad>", cadence);
+            }
+            gpx.push_str("          </gpxtpx:TrackPointExtension>\n");
+        }
+        if let Some(power) = power {
+            let _ = writeln!(gpx, "          <power>{}</power>", power);
+        }
+        gpx.push_str("        </extensions>\n");
+    }
+    gpx.push_str("      </trkpt>\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::{scaled_field, FitBuilder};
+    use crate::convert::gpx::TrackSplit;
+    use crate::protocol::data_field::DataField;
+    use crate::protocol::message_type::MessageType;
+    use crate::protocol::value::Value;
+    use crate::Fit;
+
+    #[test]
+    fn to_gpx_test() {
+        let start = 1_695_966_561;
+        let mut builder = FitBuilder::new();
+        builder.push(
+            MessageType::FileId,
+            vec![DataField::new(0, Value::Enum("activity"))],
+        );
+        let points = [
+            Some((30.5, 114.25, 10.0)),
+            None,
+            Some((30.25, 114.5, 12.0)),
+            Some((30.125, 114.5, 14.0)),
+        ];
+        for (t, point) in points.iter().enumerate() {
+            let mut fields = vec![DataField::new(253, Value::Time(start + t as u32))];
+            if let Some((lat, long, altitude)) = point {
+                fields.extend([
+                    DataField::new(0, Value::F32(*lat)),
+                    DataField::new(1, Value::F32(*long)),
+                    scaled_field(MessageType::Record, 2, *altitude),
+                ]);
+            }
+            builder.push(MessageType::Record, fields);
+        }
+        for lap_start in [start, start + 2] {
+            builder.push(
+                MessageType::Lap,
+                vec![DataField::new(2, Value::Time(lap_start))],
+            );
+        }
+        builder.push(
+            MessageType::Session,
+            vec![
+                DataField::new(2, Value::Time(start)),
+                DataField::new(5, Value::Enum("cycling")),
+            ],
+        );
+        let fit = Fit::read(builder.build().to_bytes().unwrap()).unwrap();
+
+        // Positions are decoded as f32 degrees, so compare them to within a metre or so.
+        let positions = |gpx: &str| -> Vec<(f64, f64)> {
+            gpx.lines()
+                .filter_map(|line| line.trim().strip_prefix("<trkpt lat=\""))
+                .map(|rest| {
+                    let (lat, rest) = rest.split_once("\" lon=\"").unwrap();
+                    let long = rest.trim_end_matches("\">");
+                    (lat.parse().unwrap(), long.parse().unwrap())
+                })
+                .collect()
+        };
+        let close =
+            |a: (f64, f64), b: (f64, f64)| (a.0 - b.0).abs() < 1e-5 && (a.1 - b.1).abs() < 1e-5;
+
+        let gpx = fit.to_gpx(TrackSplit::Session);
+        assert_eq!(gpx.matches("<trk>").count(), 1);
+        assert_eq!(gpx.matches("<trkseg>").count(), 1);
+        assert!(gpx.contains("<type>cycling</type>"));
+        let expected = [(30.5, 114.25), (30.25, 114.5), (30.125, 114.5)];
+        let found = positions(&gpx);
+        assert_eq!(found.len(), 3);
+        assert!(found.iter().zip(&expected).all(|(a, b)| close(*a, *b)));
+        assert!(gpx.contains("<ele>10.0</ele>\n        <time>2023-09-29T05:49:21Z</time>"));
+        assert!(gpx.contains("<ele>12.0</ele>\n        <time>2023-09-29T05:49:23Z</time>"));
+        assert!(gpx.contains("<ele>14.0</ele>\n        <time>2023-09-29T05:49:24Z</time>"));
+        // The second record has no position.
+        assert!(!gpx.contains("2023-09-29T05:49:22Z"));
+
+        let gpx = fit.to_gpx(TrackSplit::Lap);
+        assert_eq!(gpx.matches("<trk>").count(), 1);
+        assert_eq!(gpx.matches("<trkseg>").count(), 2);
+        let (first, second) = gpx.split_once("</trkseg>").unwrap();
+        assert_eq!(positions(first).len(), 1);
+        assert!(close(positions(first)[0], expected[0]));
+        assert_eq!(positions(second).len(), 2);
+        assert!(second.contains("<time>2023-09-29T05:49:23Z</time>"));
+    }
+}
@@ -0,0 +1,6 @@
+pub mod course;
+pub mod gpx;
+pub mod tcx;
+mod xml;
+
+pub use crate::protocol::time::{format_time, parse_time};
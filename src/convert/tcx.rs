@@ -0,0 +1,734 @@
+use crate::builder::{scaled_field, FitBuilder};
+use crate::protocol::data_field::DataField;
+use crate::protocol::io::invalid_data;
+use crate::protocol::macros::{get_field_scaled, get_field_time, get_field_value, get_position};
+use crate::protocol::message_type::MessageType;
+use crate::protocol::time::{format_time, parse_time};
+use crate::protocol::value::Value;
+use crate::protocol::FitMessage;
+use crate::Fit;
+use binrw::BinResult;
+use roxmltree::{Document, Node};
+use std::fmt::Write;
+use std::fs::write;
+use std::path::Path;
+
+const TCX_HEADER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<TrainingCenterDatabase xmlns="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2" xmlns:ns3="http://www.garmin.com/xmlschemas/ActivityExtension/v2" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2 http://www.garmin.com/xmlschemas/TrainingCenterDatabasev2.xsd">
+  <Activities>
+"#;
+
+impl Fit {
+    /// Converts the activity into a TCX document with one `<Activity>` per `Session`, one
+    /// `<Lap>` per `Lap` and a `<Trackpoint>` per `Record`.
+    ///
+    /// Files without laps or sessions get a single lap or activity spanning all records.
+    pub fn to_tcx(&self) -> String {
+        let mut sessions = vec![];
+        let mut laps = vec![];
+        let mut records = vec![];
+        for message in &self.data {
+            if let FitMessage::Data(msg) = message {
+                let values = msg.data.values.as_slice();
+                match msg.data.message_type {
+                    MessageType::Session => sessions.push(values),
+                    MessageType::Lap => laps.push(values),
+                    MessageType::Record if get_field_time(253, values).is_some() => {
+                        records.push(values)
+                    }
+                    _ => {}
+                }
+            }
+        }
+        sessions.sort_by_key(|values| get_field_time(2, values));
+        laps.sort_by_key(|values| get_field_time(2, values));
+
+        let lap_starts: Vec<Option<u32>> = laps.iter().map(|v| get_field_time(2, v)).collect();
+        let mut lap_records: Vec<Vec<&[DataField]>> = vec![vec![]; laps.len().max(1)];
+        for record in records {
+            let timestamp = get_field_time(253, record).unwrap_or_default();
+            lap_records[containing(&lap_starts, timestamp)].push(record);
+        }
+
+        let session_starts: Vec<Option<u32>> =
+            sessions.iter().map(|v| get_field_time(2, v)).collect();
+        let mut session_laps: Vec<Vec<usize>> = vec![vec![]; sessions.len().max(1)];
+        for lap in 0..lap_records.len() {
+            let session = match lap_starts.get(lap).copied().flatten() {
+                Some(start) => containing(&session_starts, start),
+                None => 0,
+            };
+            session_laps[session].push(lap);
+        }
+
+        let mut tcx = String::from(TCX_HEADER);
+        for (session, lap_indexes) in session_laps.iter().enumerate() {
+            let values = sessions.get(session).copied().unwrap_or_default();
+            let sport = match get_field_value(5, values) {
+                Some(Value::Enum("running")) => "Running",
+                Some(Value::Enum("cycling")) => "Biking",
+                _ => "Other",
+            };
+            let id = get_field_time(2, values).or_else(|| {
+                lap_indexes
+                    .iter()
+                    .find_map(|lap| lap_records[*lap].first())
+                    .and_then(|record| get_field_time(253, record))
+            });
+            let _ = writeln!(tcx, "    <Activity Sport=\"{}\">", sport);
+            let _ = writeln!(
+                tcx,
+                "      <Id>{}</Id>",
+                format_time(id.unwrap_or_default())
+            );
+            for lap in lap_indexes {
+                write_lap(&mut tcx, laps.get(*lap).copied(), &lap_records[*lap]);
+            }
+            tcx.push_str("    </Activity>\n");
+        }
+        tcx.push_str("  </Activities>\n</TrainingCenterDatabase>\n");
+        tcx
+    }
+
+    pub fn write_tcx<P: AsRef<Path>>(&self, path: P) -> BinResult<()> {
+        write(path, self.to_tcx())?;
+        Ok(())
+    }
+
+    /// Builds an activity file from a TCX document: a `FileId`, timer start/stop `Event`s,
+    /// a `Record` per trackpoint, a `Lap` per lap, a `Session` per activity and a closing
+    /// `Activity` message.
+    pub fn from_tcx(xml: &str) -> BinResult<Fit> {
+        let doc = Document::parse(xml).map_err(|e| invalid_data(e.to_string()))?;
+        let activities: Vec<Node> = doc
+            .descendants()
+            .filter(|n| {
+                n.has_tag_name("Activity")
+                    && n.parent().is_some_and(|p| p.has_tag_name("Activities"))
+            })
+            .collect();
+        if activities.is_empty() {
+            return Err(invalid_data("TCX document has no activities"));
+        }
+
+        let mut builder = FitBuilder::new();
+        let mut num_sessions = 0_u16;
+        let mut total_timer_time = 0.0;
+        let mut last_timestamp = 0;
+        let mut lap_index = 0_u16;
+        for activity in &activities {
+            let laps: Vec<TcxLap> = activity
+                .children()
+                .filter(|n| n.has_tag_name("Lap"))
+                .map(TcxLap::parse)
+                .filter_map(Result::transpose)
+                .collect::<BinResult<_>>()?;
+            let first = match laps.first() {
+                Some(lap) => lap.start,
+                None => continue,
+            };
+            if num_sessions == 0 {
+                builder.push(
+                    MessageType::FileId,
+                    vec![
+                        DataField::new(0, Value::Enum("activity")),
+                        DataField::new(1, Value::Enum("development")),
+                        DataField::new(2, Value::U16(0)),
+                        DataField::new(4, Value::Time(first)),
+                    ],
+                );
+            }
+            builder.push_timer_event(first, "start");
+
+            let sport = match activity.attribute("Sport") {
+                Some("Running") => "running",
+                Some("Biking") => "cycling",
+                _ => "generic",
+            };
+            let mut summary = TcxLap::empty(first);
+            let first_lap_index = lap_index;
+            for lap in &laps {
+                for point in &lap.points {
+                    builder.push(MessageType::Record, point.to_fields());
+                }
+                builder.push(MessageType::Lap, lap.lap_fields(lap_index, sport));
+                summary.add(lap);
+                lap_index = lap_index
+                    .checked_add(1)
+                    .ok_or_else(|| invalid_data("TCX document has too many laps"))?;
+            }
+            builder.push_timer_event(summary.end, "stop_all");
+            let start_position = laps
+                .iter()
+                .flat_map(|lap| &lap.points)
+                .find_map(|p| p.position);
+            builder.push(
+                MessageType::Session,
+                summary.session_fields(num_sessions, sport, first_lap_index, start_position),
+            );
+
+            total_timer_time += summary.total_time;
+            last_timestamp = last_timestamp.max(summary.end);
+            num_sessions = num_sessions
+                .checked_add(1)
+                .ok_or_else(|| invalid_data("TCX document has too many activities"))?;
+        }
+        if num_sessions == 0 {
+            return Err(invalid_data("TCX activities have no laps"));
+        }
+
+        builder.push(
+            MessageType::Activity,
+            vec![
+                DataField::new(253, Value::Time(last_timestamp)),
+                scaled_field(MessageType::Activity, 0, total_timer_time),
+                DataField::new(1, Value::U16(num_sessions)),
+                DataField::new(2, Value::Enum("manual")),
+                DataField::new(3, Value::Enum("activity")),
+                DataField::new(4, Value::Enum("stop")),
+            ],
+        );
+        Ok(builder.build())
+    }
+}
+
+/// Index of the last span starting at or before `time`, with `starts` sorted missing ones
+/// first. Earlier times go to the first span with a start; spans without one get nothing
+/// unless none has a start.
+fn containing(starts: &[Option<u32>], time: u32) -> usize {
+    let untimed = starts.partition_point(Option::is_none);
+    let timed = starts[untimed..].partition_point(|start| start.is_some_and(|s| s <= time));
+    match timed {
+        0 if untimed < starts.len() => untimed,
+        0 => 0,
+        n => untimed + n - 1,
+    }
+}
+
+fn write_lap(tcx: &mut String, lap: Option<&[DataField]>, records: &[&[DataField]]) {
+    let lap = lap.unwrap_or_default();
+    let scaled = |field_num| get_field_scaled(MessageType::Lap, field_num, lap);
+    let first = records.first().and_then(|r| get_field_time(253, r));
+    let last = records.last().and_then(|r| get_field_time(253, r));
+    let start = get_field_time(2, lap).or(first).unwrap_or_default();
+    let total_time = scaled(8)
+        .or_else(|| Some(last? as f64 - first? as f64))
+        .unwrap_or_default();
+    let distance = scaled(9)
+        .or_else(|| {
+            let first = get_field_scaled(MessageType::Record, 5, records.first()?)?;
+            let last = get_field_scaled(MessageType::Record, 5, records.last()?)?;
+            Some(last - first)
+        })
+        .unwrap_or_default();
+
+    let _ = writeln!(tcx, "      <Lap StartTime=\"{}\">", format_time(start));
+    let _ = writeln!(
+        tcx,
+        "        <TotalTimeSeconds>{}</TotalTimeSeconds>",
+        total_time
+    );
+    let _ = writeln!(tcx, "        <DistanceMeters>{}</DistanceMeters>", distance);
+    if let Some(max_speed) = scaled(111).or_else(|| scaled(14)) {
+        let _ = writeln!(tcx, "        <MaximumSpeed>{}</MaximumSpeed>", max_speed);
+    }
+    let calories = scaled(11).unwrap_or_default();
+    let _ = writeln!(tcx, "        <Calories>{}</Calories>", calories);
+    if let Some(avg_hr) = scaled(15) {
+        let _ = writeln!(
+            tcx,
+            "        <AverageHeartRateBpm><Value>{}</Value></AverageHeartRateBpm>",
+            avg_hr
+        );
+    }
+    if let Some(max_hr) = scaled(16) {
+        let _ = writeln!(
+            tcx,
+            "        <MaximumHeartRateBpm><Value>{}</Value></MaximumHeartRateBpm>",
+            max_hr
+        );
+    }
+    let intensity = match get_field_value(23, lap) {
+        Some(Value::Enum("rest")) => "Resting",
+        _ => "Active",
+    };
+    let _ = writeln!(tcx, "        <Intensity>{}</Intensity>", intensity);
+    if let Some(cadence) = scaled(17) {
+        let _ = writeln!(tcx, "        <Cadence>{}</Cadence>", cadence);
+    }
+    let trigger = match get_field_value(24, lap) {
+        Some(Value::Enum("distance")) => "Distance",
+        Some(Value::Enum("time")) => "Time",
+        Some(Value::Enum(
+            "position_start" | "position_lap" | "position_waypoint" | "position_marked",
+        )) => "Location",
+        _ => "Manual",
+    };
+    let _ = writeln!(tcx, "        <TriggerMethod>{}</TriggerMethod>", trigger);
+
+    if !records.is_empty() {
+        tcx.push_str("        <Track>\n");
+        for record in records {
+            write_trackpoint(tcx, record);
+        }
+        tcx.push_str("        </Track>\n");
+    }
+
+    let avg_speed = scaled(110).or_else(|| scaled(13));
+    let avg_power = scaled(19);
+    let max_power = scaled(20);
+    if avg_speed.is_some() || avg_power.is_some() || max_power.is_some() {
+        tcx.push_str("        <Extensions>\n          <ns3:LX>\n");
+        if let Some(avg_speed) = avg_speed {
+            let _ = writeln!(
+                tcx,
+                "            <ns3:AvgSpeed>{}</ns3:AvgSpeed>",
+                avg_speed
+            );
+        }
+        if let Some(avg_power) = avg_power {
+            let _ = writeln!(
+                tcx,
+                "            <ns3:AvgWatts>{}</ns3:AvgWatts>",
+                avg_power
+            );
+        }
+        if let Some(max_power) = max_power {
+            let _ = writeln!(
+                tcx,
+                "            <ns3:MaxWatts>{}</ns3:MaxWatts>",
+                max_power
+            );
+        }
+        tcx.push_str("          </ns3:LX>\n        </Extensions>\n");
+    }
+    tcx.push_str("      </Lap>\n");
+}
+
+fn write_trackpoint(tcx: &mut String, values: &[DataField]) {
+    let scaled = |field_num| get_field_scaled(MessageType::Record, field_num, values);
+    tcx.push_str("          <Trackpoint>\n");
+    let timestamp = get_field_time(253, values).unwrap_or_default();
+    let _ = writeln!(tcx, "            <Time>{}</Time>", format_time(timestamp));
+    if let Some((lat, long)) = get_position(0, 1, values) {
+        let _ = writeln!(
+            tcx,
+            "            <Position><LatitudeDegrees>{:.7}</LatitudeDegrees><LongitudeDegrees>{:.7}</LongitudeDegrees></Position>",
+            lat, long
+        );
+    }
+    if let Some(altitude) = scaled(78).or_else(|| scaled(2)) {
+        let _ = writeln!(
+            tcx,
+            "            <AltitudeMeters>{:.1}</AltitudeMeters>",
+            altitude
+        );
+    }
+    if let Some(distance) = scaled(5) {
+        let _ = writeln!(
+            tcx,
+            "            <DistanceMeters>{:.2}</DistanceMeters>",
+            distance
+        );
+    }
+    if let Some(heart_rate) = scaled(3) {
+        let _ = writeln!(
+            tcx,
+            "            <HeartRateBpm><Value>{}</Value></HeartRateBpm>",
+            heart_rate
+        );
+    }
+    if let Some(cadence) = scaled(4) {
+        let _ = writeln!(tcx, "            <Cadence>{}</Cadence>", cadence);
+    }
+    let speed = scaled(73).or_else(|| scaled(6));
+    let power = scaled(7);
+    if speed.is_some() || power.is_some() {
+        tcx.push_str("            <Extensions>\n              <ns3:TPX>\n");
+        if let Some(speed) = speed {
+            let _ = writeln!(tcx, "                <ns3:Speed>{:.3}</ns3:Speed>", speed);
+        }
+        if let Some(power) = power {
+            let _ = writeln!(tcx, "                <ns3:Watts>{}</ns3:Watts>", power);
+        }
+        tcx.push_str("              </ns3:TPX>\n            </Extensions>\n");
+    }
+    tcx.push_str("          </Trackpoint>\n");
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name(name))
+}
+
+fn child_f64(node: Node, path: &[&str]) -> Option<f64> {
+    let mut node = node;
+    for name in path {
+        node = child(node, name)?;
+    }
+    node.text()?.trim().parse().ok()
+}
+
+fn extension_f64(node: Node, name: &str) -> Option<f64> {
+    child(node, "Extensions")?
+        .descendants()
+        .find(|n| n.has_tag_name(name))?
+        .text()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+struct TcxPoint {
+    time: u32,
+    position: Option<(f64, f64)>,
+    altitude: Option<f64>,
+    distance: Option<f64>,
+    heart_rate: Option<f64>,
+    cadence: Option<f64>,
+    speed: Option<f64>,
+    power: Option<f64>,
+}
+
+impl TcxPoint {
+    fn parse(node: Node) -> Option<Self> {
+        let time = parse_time(child(node, "Time")?.text()?)?;
+        let position = child_f64(node, &["Position", "LatitudeDegrees"])
+            .zip(child_f64(node, &["Position", "LongitudeDegrees"]));
+        Some(Self {
+            time,
+            position,
+            altitude: child_f64(node, &["AltitudeMeters"]),
+            distance: child_f64(node, &["DistanceMeters"]),
+            heart_rate: child_f64(node, &["HeartRateBpm", "Value"]),
+            cadence: child_f64(node, &["Cadence"]),
+            speed: extension_f64(node, "Speed"),
+            power: extension_f64(node, "Watts"),
+        })
+    }
+
+    fn to_fields(&self) -> Vec<DataField> {
+        let record = |field_num, value: Option<f64>| {
+            value.map(|value| scaled_field(MessageType::Record, field_num, value))
+        };
+        let mut fields = vec![DataField::new(253, Value::Time(self.time))];
+        if let Some((lat, long)) = self.position {
+            fields.push(DataField::new(0, Value::F32(lat as f32)));
+            fields.push(DataField::new(1, Value::F32(long as f32)));
+        }
+        fields.extend(
+            [
+                record(2, self.altitude),
+                record(3, self.heart_rate),
+                record(4, self.cadence),
+                record(5, self.distance),
+                record(6, self.speed),
+                record(7, self.power),
+            ]
+            .into_iter()
+            .flatten(),
+        );
+        fields
+    }
+}
+
+struct TcxLap {
+    start: u32,
+    end: u32,
+    num_laps: u16,
+    total_time: f64,
+    distance: f64,
+    calories: f64,
+    max_speed: Option<f64>,
+    avg_heart_rate: Option<f64>,
+    max_heart_rate: Option<f64>,
+    cadence: Option<f64>,
+    avg_power: Option<f64>,
+    max_power: Option<f64>,
+    intensity: &'static str,
+    trigger: &'static str,
+    points: Vec<TcxPoint>,
+}
+
+impl TcxLap {
+    fn empty(start: u32) -> Self {
+        Self {
+            start,
+            end: start,
+            num_laps: 0,
+            total_time: 0.0,
+            distance: 0.0,
+            calories: 0.0,
+            max_speed: None,
+            avg_heart_rate: None,
+            max_heart_rate: None,
+            cadence: None,
+            avg_power: None,
+            max_power: None,
+            intensity: "active",
+            trigger: "session_end",
+            points: vec![],
+        }
+    }
+
+    /// Parses a `<Lap>`, giving `None` for a lap with neither a start time nor trackpoints.
+    fn parse(node: Node) -> BinResult<Option<Self>> {
+        let points: Vec<TcxPoint> = node
+            .children()
+            .filter(|n| n.has_tag_name("Track"))
+            .flat_map(|track| track.children().filter(|n| n.has_tag_name("Trackpoint")))
+            .filter_map(TcxPoint::parse)
+            .collect();
+        let start = match node
+            .attribute("StartTime")
+            .and_then(parse_time)
+            .or_else(|| points.first().map(|p| p.time))
+        {
+            Some(start) => start,
+            None => return Ok(None),
+        };
+        let last = points.last().map(|p| p.time).unwrap_or(start).max(start);
+        let distance = points.iter().rev().find_map(|p| p.distance);
+        let total_time = match child_f64(node, &["TotalTimeSeconds"]) {
+            Some(t) if !t.is_finite() || t < 0.0 => {
+                return Err(invalid_data("TCX lap has an invalid TotalTimeSeconds"))
+            }
+            Some(t) => t,
+            None => (last - start) as f64,
+        };
+        Ok(Some(Self {
+            start,
+            end: last.max(start.saturating_add(total_time.round() as u32)),
+            num_laps: 1,
+            total_time,
+            distance: child_f64(node, &["DistanceMeters"])
+                .or(distance)
+                .unwrap_or_default(),
+            calories: child_f64(node, &["Calories"]).unwrap_or_default(),
+            max_speed: child_f64(node, &["MaximumSpeed"]),
+            avg_heart_rate: child_f64(node, &["AverageHeartRateBpm", "Value"]),
+            max_heart_rate: child_f64(node, &["MaximumHeartRateBpm", "Value"]),
+            cadence: child_f64(node, &["Cadence"]),
+            avg_power: extension_f64(node, "AvgWatts"),
+            max_power: extension_f64(node, "MaxWatts"),
+            intensity: match child(node, "Intensity").and_then(|n| n.text()) {
+                Some("Resting") => "rest",
+                _ => "active",
+            },
+            trigger: match child(node, "TriggerMethod").and_then(|n| n.text()) {
+                Some("Distance") => "distance",
+                Some("Location") => "position_lap",
+                Some("Time") => "time",
+                _ => "manual",
+            },
+            points,
+        }))
+    }
+
+    /// Folds another lap into this one as the running session summary.
+    fn add(&mut self, lap: &TcxLap) {
+        let weighted = |a: Option<f64>, a_time: f64, b: Option<f64>| match (a, b) {
+            (Some(a), Some(b)) if a_time + lap.total_time > 0.0 => {
+                Some((a * a_time + b * lap.total_time) / (a_time + lap.total_time))
+            }
+            (a, b) => a.or(b),
+        };
+        let max = |a: Option<f64>, b: Option<f64>| match (a, b) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        self.avg_heart_rate = weighted(self.avg_heart_rate, self.total_time, lap.avg_heart_rate);
+        self.cadence = weighted(self.cadence, self.total_time, lap.cadence);
+        self.avg_power = weighted(self.avg_power, self.total_time, lap.avg_power);
+        self.max_speed = max(self.max_speed, lap.max_speed);
+        self.max_heart_rate = max(self.max_heart_rate, lap.max_heart_rate);
+        self.max_power = max(self.max_power, lap.max_power);
+        self.total_time += lap.total_time;
+        self.distance += lap.distance;
+        self.calories += lap.calories;
+        self.end = self.end.max(lap.end);
+        self.num_laps += 1;
+    }
+
+    fn avg_speed(&self) -> Option<f64> {
+        (self.total_time > 0.0).then(|| self.distance / self.total_time)
+    }
+
+    fn lap_fields(&self, message_index: u16, sport: &'static str) -> Vec<DataField> {
+        let lap = |field_num, value: Option<f64>| {
+            value.map(|value| scaled_field(MessageType::Lap, field_num, value))
+        };
+        let mut fields = vec![
+            DataField::new(0, Value::Enum("lap")),
+            DataField::new(1, Value::Enum("stop")),
+            DataField::new(253, Value::Time(self.end)),
+            DataField::new(2, Value::Time(self.start)),
+            DataField::new(254, Value::U16(message_index)),
+        ];
+        let start = self.points.iter().find_map(|p| p.position);
+        let end = self.points.iter().rev().find_map(|p| p.position);
+        if let (Some(start), Some(end)) = (start, end) {
+            fields.push(DataField::new(3, Value::F32(start.0 as f32)));
+            fields.push(DataField::new(4, Value::F32(start.1 as f32)));
+            fields.push(DataField::new(5, Value::F32(end.0 as f32)));
+            fields.push(DataField::new(6, Value::F32(end.1 as f32)));
+        }
+        fields.extend(
+            [
+                lap(7, Some(self.total_time)),
+                lap(8, Some(self.total_time)),
+                lap(9, Some(self.distance)),
+                lap(11, Some(self.calories)),
+                lap(13, self.avg_speed()),
+                lap(14, self.max_speed),
+                lap(15, self.avg_heart_rate),
+                lap(16, self.max_heart_rate),
+                lap(17, self.cadence),
+                lap(19, self.avg_power),
+                lap(20, self.max_power),
+            ]
+            .into_iter()
+            .flatten(),
+        );
+        fields.push(DataField::new(23, Value::Enum(self.intensity)));
+        fields.push(DataField::new(24, Value::Enum(self.trigger)));
+        fields.push(DataField::new(25, Value::Enum(sport)));
+        fields
+    }
+
+    fn session_fields(
+        &self,
+        message_index: u16,
+        sport: &'static str,
+        first_lap_index: u16,
+        start_position: Option<(f64, f64)>,
+    ) -> Vec<DataField> {
+        let session = |field_num, value: Option<f64>| {
+            value.map(|value| scaled_field(MessageType::Session, field_num, value))
+        };
+        let mut fields = vec![
+            DataField::new(0, Value::Enum("session")),
+            DataField::new(1, Value::Enum("stop")),
+            DataField::new(253, Value::Time(self.end)),
+            DataField::new(2, Value::Time(self.start)),
+            DataField::new(254, Value::U16(message_index)),
+            DataField::new(5, Value::Enum(sport)),
+        ];
+        if let Some((lat, long)) = start_position {
+            fields.push(DataField::new(3, Value::F32(lat as f32)));
+            fields.push(DataField::new(4, Value::F32(long as f32)));
+        }
+        fields.extend(
+            [
+                session(7, Some(self.total_time)),
+                session(8, Some(self.total_time)),
+                session(9, Some(self.distance)),
+                session(11, Some(self.calories)),
+                session(14, self.avg_speed()),
+                session(15, self.max_speed),
+                session(16, self.avg_heart_rate),
+                session(17, self.max_heart_rate),
+                session(18, self.cadence),
+                session(20, self.avg_power),
+                session(21, self.max_power),
+            ]
+            .into_iter()
+            .flatten(),
+        );
+        fields.push(DataField::new(25, Value::U16(first_lap_index)));
+        fields.push(DataField::new(26, Value::U16(self.num_laps)));
+        fields.push(DataField::new(28, Value::Enum("activity_end")));
+        fields
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::data_field::DataField;
+    use crate::protocol::message_type::MessageType;
+    use crate::protocol::value::Value;
+    use crate::test_util::{recording, START};
+    use crate::Fit;
+
+    const TCX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<TrainingCenterDatabase xmlns="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2">
+  <Activities>
+    <Activity Sport="Biking">
+      <Id>2023-09-29T05:49:21Z</Id>
+      <Lap StartTime="2023-09-29T05:49:21Z">
+        <TotalTimeSeconds>2</TotalTimeSeconds>
+        <DistanceMeters>12.5</DistanceMeters>
+        <Calories>1</Calories>
+        <Intensity>Active</Intensity>
+        <TriggerMethod>Manual</TriggerMethod>
+        <Track>
+          <Trackpoint>
+            <Time>2023-09-29T05:49:21Z</Time>
+            <Position><LatitudeDegrees>30.5</LatitudeDegrees><LongitudeDegrees>114.25</LongitudeDegrees></Position>
+            <AltitudeMeters>21.4</AltitudeMeters>
+            <DistanceMeters>0</DistanceMeters>
+            <HeartRateBpm><Value>120</Value></HeartRateBpm>
+          </Trackpoint>
+          <Trackpoint>
+            <Time>2023-09-29T05:49:23Z</Time>
+            <Position><LatitudeDegrees>30.5001</LatitudeDegrees><LongitudeDegrees>114.2501</LongitudeDegrees></Position>
+            <AltitudeMeters>21.6</AltitudeMeters>
+            <DistanceMeters>12.5</DistanceMeters>
+            <HeartRateBpm><Value>122</Value></HeartRateBpm>
+          </Trackpoint>
+        </Track>
+      </Lap>
+    </Activity>
+  </Activities>
+</TrainingCenterDatabase>"#;
+
+    #[test]
+    fn tcx_round_trip_test() {
+        let fit = Fit::from_tcx(TCX).unwrap();
+        let fit = Fit::read(fit.to_bytes().unwrap()).unwrap();
+        let count = |message_type| fit.messages(message_type).count();
+        assert_eq!(count(MessageType::FileId), 1);
+        assert_eq!(count(MessageType::Record), 2);
+        assert_eq!(count(MessageType::Lap), 1);
+        assert_eq!(count(MessageType::Session), 1);
+        assert_eq!(count(MessageType::Activity), 1);
+
+        let tcx = fit.to_tcx();
+        assert!(tcx.contains(r#"<Activity Sport="Biking">"#));
+        assert!(tcx.contains("<Time>2023-09-29T05:49:23Z</Time>"));
+        assert!(tcx.contains("<DistanceMeters>12.5</DistanceMeters>"));
+        assert!(tcx.contains("<AltitudeMeters>21.6</AltitudeMeters>"));
+        assert!(tcx.contains("<HeartRateBpm><Value>122</Value></HeartRateBpm>"));
+
+        let long = TCX.replace(
+            "<TotalTimeSeconds>2</TotalTimeSeconds>",
+            "<TotalTimeSeconds>1e12</TotalTimeSeconds>",
+        );
+        assert!(Fit::from_tcx(&long).is_ok());
+        for total_time in ["-1", "NaN", "inf"] {
+            let bad = TCX.replace(
+                "<TotalTimeSeconds>2</TotalTimeSeconds>",
+                &format!("<TotalTimeSeconds>{}</TotalTimeSeconds>", total_time),
+            );
+            assert!(Fit::from_tcx(&bad).is_err());
+        }
+    }
+
+    #[test]
+    fn to_tcx_lap_without_start_test() {
+        let mut builder = recording([0, 10], |_| vec![]);
+        for (end, start) in [(5, Some(0)), (10, None), (10, Some(8))] {
+            let mut values = vec![DataField::new(253, Value::Time(START + end))];
+            if let Some(start) = start {
+                values.push(DataField::new(2, Value::Time(START + start)));
+            }
+            builder.push(MessageType::Lap, values);
+        }
+        let tcx = builder.build().to_tcx();
+        let points: Vec<_> = tcx
+            .split("<Lap ")
+            .skip(1)
+            .map(|lap| lap.matches("<Trackpoint>").count())
+            .collect();
+        assert_eq!(points, vec![0, 1, 1]);
+    }
+}
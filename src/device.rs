@@ -0,0 +1,291 @@
+use crate::builder::FitBuilder;
+use crate::protocol::data_field::DataField;
+use crate::protocol::get_field_string_value::{get_field_key_from_string, FieldType};
+use crate::protocol::io::invalid_data;
+use crate::protocol::macros::{get_field_value, put_field_scaled};
+use crate::protocol::message_type::MessageType;
+use crate::protocol::value::Value;
+use crate::protocol::FitMessage;
+use crate::{put_field, Fit};
+use binrw::BinResult;
+
+/// `garmin_product` values from the FIT SDK profile for the devices and apps files are most
+/// often attributed to.
+pub const GARMIN_PRODUCTS: &[(&str, u16)] = &[
+    ("edge500", 1036),
+    ("edge800", 1169),
+    ("edge510", 1561),
+    ("edge810", 1567),
+    ("fr920xt", 1765),
+    ("edge1000", 1836),
+    ("fenix3", 2050),
+    ("edge520", 2067),
+    ("fr630", 2156),
+    ("fr735xt", 2158),
+    ("index_smart_scale", 2429),
+    ("fr235", 2431),
+    ("edge_820", 2530),
+    ("fr935", 2691),
+    ("fenix5", 2697),
+    ("vivoactive3", 2700),
+    ("edge_1030", 2713),
+    ("vector_3", 2787),
+    ("fr645", 2886),
+    ("fr645m", 2888),
+    ("edge_130", 2909),
+    ("fr245", 3076),
+    ("fr245m", 3077),
+    ("fenix5_plus", 3110),
+    ("edge_520_plus", 3112),
+    ("fr945", 3113),
+    ("edge_530", 3121),
+    ("edge_830", 3122),
+    ("venu", 3226),
+    ("fenix6", 3290),
+    ("hrm_dual", 3299),
+    ("hrm_pro", 3300),
+    ("edge_130_plus", 3558),
+    ("edge_1030_plus", 3570),
+    ("fr745", 3589),
+    ("venu2", 3703),
+    ("edge_1040", 3843),
+    ("fr55", 3869),
+    ("fenix7", 3906),
+    ("fr255", 3992),
+    ("fr955", 4024),
+    ("hrm_pro_plus", 4130),
+    ("edge_explore2", 4169),
+    ("fr265_large", 4257),
+    ("fr265_small", 4258),
+    ("venu3", 4260),
+    ("fr965", 4315),
+    ("fr165", 4432),
+    ("edge_1050", 4440),
+    ("fenix8", 4536),
+    ("sdm4", 10007),
+    ("edge_remote", 10014),
+    ("training_center", 20119),
+    ("tacx_training_app_win", 20533),
+    ("tacx_training_app_mac", 20534),
+    ("tacx_training_app_android", 30045),
+    ("tacx_training_app_ios", 30046),
+    ("connect", 65534),
+];
+
+/// Looks up the product number of a Garmin device by its profile name, e.g. `"edge_530"`.
+pub fn garmin_product(name: &str) -> Option<u16> {
+    GARMIN_PRODUCTS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, product)| *product)
+}
+
+pub fn garmin_product_name(product: u16) -> Option<&'static str> {
+    GARMIN_PRODUCTS
+        .iter()
+        .find(|(_, p)| *p == product)
+        .map(|(name, _)| *name)
+}
+
+/// The device a file is attributed to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceIdentity {
+    /// A `Manufacturer` profile value such as `"garmin"`.
+    pub manufacturer: &'static str,
+    pub product: u16,
+    pub serial_number: Option<u32>,
+    /// Firmware version, e.g. `9.8`.
+    pub software_version: Option<f64>,
+}
+
+impl DeviceIdentity {
+    /// A Garmin device by its `garmin_product` name.
+    pub fn garmin(product: &str) -> Option<Self> {
+        Some(DeviceIdentity {
+            manufacturer: "garmin",
+            product: garmin_product(product)?,
+            serial_number: None,
+            software_version: None,
+        })
+    }
+}
+
+impl Fit {
+    /// Attributes the file to `device`, rewriting manufacturer, product and serial number of
+    /// the `FileId` and of the creator's `DeviceInfo`, along with its software version.
+    ///
+    /// Sensor `DeviceInfo` messages are kept. Fields the messages lack are added, and a
+    /// creator `DeviceInfo` is inserted after the `FileId` when the file has none.
+    pub fn set_device(&mut self, device: &DeviceIdentity) -> BinResult<()> {
+        if get_field_key_from_string(FieldType::Manufacturer, device.manufacturer).is_none() {
+            return Err(invalid_data(format!(
+                "unknown manufacturer {}",
+                device.manufacturer
+            )));
+        }
+        let file_id = self
+            .data
+            .iter()
+            .position(|m| match m {
+                FitMessage::Data(msg) => msg.data.message_type == MessageType::FileId,
+                FitMessage::Definition(_) => false,
+            })
+            .ok_or_else(|| invalid_data("file has no FileId"))?;
+        let mut creator = false;
+        for message in &mut self.data {
+            let msg = match message {
+                FitMessage::Data(msg) => msg,
+                FitMessage::Definition(_) => continue,
+            };
+            let values = &mut msg.data.values;
+            match msg.data.message_type {
+                MessageType::FileId => {
+                    set_identity(MessageType::FileId, (1, 2, 3), device, values);
+                }
+                MessageType::DeviceInfo if is_creator(values) => {
+                    creator = true;
+                    set_creator(device, values);
+                }
+                _ => {}
+            }
+        }
+        if !creator {
+            let mut values = vec![DataField::new(0, Value::U8(0))];
+            set_creator(device, &mut values);
+            let mut builder = FitBuilder::new();
+            builder.push(MessageType::DeviceInfo, values);
+            self.insert_messages(file_id + 1, builder.build().data);
+        }
+        self.define_added_fields();
+        Ok(())
+    }
+}
+
+fn is_creator(values: &[DataField]) -> bool {
+    match get_field_value(0, values) {
+        None => true,
+        Some(Value::Enum(index)) => index == "creator",
+        Some(Value::U8(index)) => index == 0,
+        Some(_) => false,
+    }
+}
+
+/// Writes the identity and software version of the creator's `DeviceInfo`.
+fn set_creator(device: &DeviceIdentity, values: &mut Vec<DataField>) {
+    set_identity(MessageType::DeviceInfo, (2, 4, 3), device, values);
+    if let Some(version) = device.software_version {
+        put_field_scaled(MessageType::DeviceInfo, 5, version, values);
+    }
+}
+
+/// Writes manufacturer, product and serial number into the given field numbers.
+fn set_identity(
+    message_type: MessageType,
+    (manufacturer, product, serial_number): (u8, u8, u8),
+    device: &DeviceIdentity,
+    values: &mut Vec<DataField>,
+) {
+    put_field!(values, manufacturer, Value::Enum(device.manufacturer));
+    put_field_scaled(message_type, product, device.product as f64, values);
+    if let Some(serial) = device.serial_number {
+        put_field_scaled(message_type, serial_number, serial as f64, values);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::FitBuilder;
+    use crate::device::DeviceIdentity;
+    use crate::protocol::data_field::DataField;
+    use crate::protocol::macros::{get_field_scaled, get_field_value};
+    use crate::protocol::message_type::MessageType;
+    use crate::protocol::value::Value;
+    use crate::protocol::FitMessage;
+    use crate::test_util::recording;
+    use crate::Fit;
+
+    #[test]
+    fn set_device_test() {
+        let mut builder = FitBuilder::new();
+        builder.push(
+            MessageType::FileId,
+            vec![
+                DataField::new(0, Value::Enum("activity")),
+                DataField::new(1, Value::Enum("development")),
+                DataField::new(2, Value::U16(0)),
+                DataField::new(3, Value::U32(1)),
+            ],
+        );
+        for (index, manufacturer) in [(0, "development"), (1, "wahoo_fitness")] {
+            builder.push(
+                MessageType::DeviceInfo,
+                vec![
+                    DataField::new(0, Value::U8(index)),
+                    DataField::new(2, Value::Enum(manufacturer)),
+                    DataField::new(4, Value::U16(0)),
+                    DataField::new(5, Value::U16(100)),
+                ],
+            );
+        }
+        let mut fit = builder.build();
+        let device = DeviceIdentity {
+            serial_number: Some(3_300_000_001),
+            software_version: Some(9.8),
+            ..DeviceIdentity::garmin("edge_530").unwrap()
+        };
+        fit.set_device(&device).unwrap();
+        let fit = Fit::read(fit.to_bytes().unwrap()).unwrap();
+
+        let mut device_infos = vec![];
+        for message in &fit.data {
+            if let FitMessage::Data(msg) = message {
+                let values = &msg.data.values;
+                match msg.data.message_type {
+                    MessageType::FileId => {
+                        assert_eq!(get_field_value(1, values), Some(Value::Enum("garmin")));
+                        assert_eq!(get_field_value(2, values), Some(Value::U16(3121)));
+                        assert_eq!(get_field_value(3, values), Some(Value::U32(3_300_000_001)));
+                    }
+                    MessageType::DeviceInfo => device_infos.push((
+                        get_field_value(2, values),
+                        get_field_scaled(MessageType::DeviceInfo, 5, values),
+                    )),
+                    _ => {}
+                }
+            }
+        }
+        assert_eq!(
+            device_infos,
+            vec![
+                (Some(Value::Enum("garmin")), Some(9.8)),
+                (Some(Value::Enum("wahoo_fitness")), Some(1.0)),
+            ]
+        );
+
+        let mut fit = recording([0], |_| vec![]).build();
+        fit.set_device(&device).unwrap();
+        let fit = Fit::read(fit.to_bytes().unwrap()).unwrap();
+        let file_id = &fit.first(MessageType::FileId).unwrap().data.values;
+        assert_eq!(get_field_value(1, file_id), Some(Value::Enum("garmin")));
+        assert_eq!(get_field_value(2, file_id), Some(Value::U16(3121)));
+        assert_eq!(get_field_value(3, file_id), Some(Value::U32(3_300_000_001)));
+        let types: Vec<_> = fit.data_messages().map(|m| m.data.message_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                MessageType::FileId,
+                MessageType::DeviceInfo,
+                MessageType::Record
+            ]
+        );
+        let creator = &fit.first(MessageType::DeviceInfo).unwrap().data.values;
+        assert_eq!(get_field_value(2, creator), Some(Value::Enum("garmin")));
+        assert_eq!(
+            get_field_scaled(MessageType::DeviceInfo, 5, creator),
+            Some(9.8)
+        );
+
+        let mut fit = FitBuilder::new().build();
+        assert!(fit.set_device(&device).is_err());
+    }
+}
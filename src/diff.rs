@@ -0,0 +1,209 @@
+use crate::protocol::data_field::DataField;
+use crate::protocol::macros::{get_field_scaled, get_field_time, get_field_value};
+use crate::protocol::message_type::MessageType;
+use crate::protocol::value::Value;
+use crate::protocol::FitMessage;
+use crate::Fit;
+use std::slice::from_ref;
+
+/// Settings for `Fit::diff`.
+#[derive(Debug, Clone, Default)]
+pub struct DiffOptions {
+    /// Numeric fields whose physical values differ by at most this much are equal.
+    pub tolerance: f64,
+}
+
+/// A difference between two files. Indexes are positions in `Fit::data`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Difference {
+    /// A message only the second file has.
+    Added {
+        index: usize,
+        message_type: MessageType,
+    },
+    /// A message only the first file has.
+    Removed {
+        index: usize,
+        message_type: MessageType,
+    },
+    /// A field that differs between matched messages, or that only one of them has.
+    Changed {
+        left: usize,
+        right: usize,
+        message_type: MessageType,
+        field_num: u8,
+        old: Option<Value>,
+        new: Option<Value>,
+    },
+}
+
+struct Entry<'a> {
+    index: usize,
+    time: Option<u32>,
+    values: &'a [DataField],
+}
+
+impl Fit {
+    /// Compares the data messages of two files field by field.
+    ///
+    /// Messages are matched per message type, by timestamp where they have one and
+    /// otherwise in file order. Definitions are not compared, so files that only differ in
+    /// how their messages are encoded have no differences.
+    pub fn diff(&self, other: &Fit, options: &DiffOptions) -> Vec<Difference> {
+        let mut message_types: Vec<MessageType> = vec![];
+        for fit in [self, other] {
+            for message in &fit.data {
+                if let FitMessage::Data(msg) = message {
+                    if !message_types.contains(&msg.data.message_type) {
+                        message_types.push(msg.data.message_type);
+                    }
+                }
+            }
+        }
+
+        let mut differences = vec![];
+        for message_type in message_types {
+            let left = entries(self, message_type);
+            let right = entries(other, message_type);
+            let (mut l, mut r) = (0, 0);
+            while l < left.len() || r < right.len() {
+                match (left.get(l), right.get(r)) {
+                    (Some(old), Some(new)) if old.time == new.time => {
+                        compare(message_type, old, new, options, &mut differences);
+                        l += 1;
+                        r += 1;
+                    }
+                    (Some(old), new) if new.is_none_or(|new| old.time < new.time) => {
+                        differences.push(Difference::Removed {
+                            index: old.index,
+                            message_type,
+                        });
+                        l += 1;
+                    }
+                    (_, Some(new)) => {
+                        differences.push(Difference::Added {
+                            index: new.index,
+                            message_type,
+                        });
+                        r += 1;
+                    }
+                    (_, None) => unreachable!(),
+                }
+            }
+        }
+        differences
+    }
+}
+
+fn entries(fit: &Fit, message_type: MessageType) -> Vec<Entry<'_>> {
+    fit.data
+        .iter()
+        .enumerate()
+        .filter_map(|(index, message)| match message {
+            FitMessage::Data(msg) if msg.data.message_type == message_type => Some(Entry {
+                index,
+                time: get_field_time(253, &msg.data.values),
+                values: &msg.data.values,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+fn compare(
+    message_type: MessageType,
+    old: &Entry,
+    new: &Entry,
+    options: &DiffOptions,
+    differences: &mut Vec<Difference>,
+) {
+    let mut field_nums: Vec<u8> = vec![];
+    for field in old.values.iter().chain(new.values) {
+        if !field_nums.contains(&field.field_num) {
+            field_nums.push(field.field_num);
+        }
+    }
+    for field_num in field_nums {
+        let old_field = old.values.iter().find(|field| field.field_num == field_num);
+        let new_field = new.values.iter().find(|field| field.field_num == field_num);
+        let equal = match (old_field, new_field) {
+            (Some(a), Some(b)) => {
+                let scaled = |field| get_field_scaled(message_type, field_num, from_ref(field));
+                match (scaled(a), scaled(b)) {
+                    (Some(a), Some(b)) => (a - b).abs() <= options.tolerance,
+                    _ => a.value == b.value,
+                }
+            }
+            _ => false,
+        };
+        if !equal {
+            differences.push(Difference::Changed {
+                left: old.index,
+                right: new.index,
+                message_type,
+                field_num,
+                old: get_field_value(field_num, old.values),
+                new: get_field_value(field_num, new.values),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diff::{DiffOptions, Difference};
+    use crate::protocol::data_field::DataField;
+    use crate::protocol::message_type::MessageType;
+    use crate::protocol::value::Value;
+    use crate::test_util;
+    use crate::Fit;
+
+    fn recording(times: &[u32], heart_rate: u8) -> Fit {
+        test_util::recording(times.iter().copied(), |t| {
+            vec![
+                DataField::new(0, Value::F32(45.0 + t as f32 * 1e-7)),
+                DataField::new(3, Value::U8(heart_rate)),
+            ]
+        })
+        .build()
+    }
+
+    #[test]
+    fn diff_test() {
+        let fit = recording(&[0, 1, 2], 150);
+        let read = Fit::read(fit.to_bytes().unwrap()).unwrap();
+        let options = DiffOptions { tolerance: 1e-5 };
+        assert_eq!(fit.diff(&read, &options), vec![]);
+
+        let other = recording(&[1, 2, 3], 151);
+        assert_eq!(
+            fit.diff(&other, &options),
+            vec![
+                Difference::Removed {
+                    index: 3,
+                    message_type: MessageType::Record
+                },
+                Difference::Changed {
+                    left: 4,
+                    right: 3,
+                    message_type: MessageType::Record,
+                    field_num: 3,
+                    old: Some(Value::U8(150)),
+                    new: Some(Value::U8(151)),
+                },
+                Difference::Changed {
+                    left: 5,
+                    right: 4,
+                    message_type: MessageType::Record,
+                    field_num: 3,
+                    old: Some(Value::U8(150)),
+                    new: Some(Value::U8(151)),
+                },
+                Difference::Added {
+                    index: 5,
+                    message_type: MessageType::Record
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,531 @@
+use crate::builder::FitBuilder;
+use crate::protocol::consts::{COMPRESSED_HEADER_TIME_OFFSET_MASK, PSEUDO_EPOCH};
+use crate::protocol::io::invalid_data;
+use crate::protocol::macros::{
+    get_field_scaled, get_field_time, get_field_value, get_position, set_field_scaled,
+};
+use crate::protocol::message_type::MessageType;
+use crate::protocol::value::Value;
+use crate::protocol::{FitDataMessage, FitMessage};
+use crate::{is_timer_stop, put_field, update_field, Fit};
+use binrw::BinResult;
+
+/// One end of a `Fit::crop` window.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CropBound {
+    /// Unix time.
+    Time(u32),
+    /// Distance from the start of the activity in metres, as recorded by the `Record`s.
+    Distance(f64),
+}
+
+impl Fit {
+    /// Keeps only the part of the activity between `start` and `end`.
+    ///
+    /// Records, events, lengths and HRV outside the window are dropped, laps and sessions
+    /// crossing a boundary are truncated to it and those entirely outside are removed. The
+    /// timer is started and stopped at the window edges, then the lap, session and activity
+    /// summaries are recomputed from the remaining records.
+    pub fn crop(&mut self, start: CropBound, end: CropBound) -> BinResult<()> {
+        let records: Vec<(u32, Option<f64>)> = self
+            .data
+            .iter()
+            .filter_map(|message| match message {
+                FitMessage::Data(msg) if msg.data.message_type == MessageType::Record => {
+                    let values = &msg.data.values;
+                    let time = get_field_time(253, values)?;
+                    Some((time, get_field_scaled(MessageType::Record, 5, values)))
+                }
+                _ => None,
+            })
+            .collect();
+        let start = match start {
+            CropBound::Time(t) => Some(t),
+            CropBound::Distance(d) => records
+                .iter()
+                .find(|(_, distance)| distance.is_some_and(|distance| distance >= d))
+                .map(|(t, _)| *t),
+        };
+        let end = match end {
+            CropBound::Time(t) => Some(t),
+            CropBound::Distance(d) => records
+                .iter()
+                .rev()
+                .find(|(_, distance)| distance.is_some_and(|distance| distance <= d))
+                .map(|(t, _)| *t),
+        };
+        let (start, end) = match (start, end) {
+            (Some(start), Some(end)) if records.iter().any(|(t, _)| (start..=end).contains(t)) => {
+                (start, end)
+            }
+            _ => return Err(invalid_data("crop window contains no records")),
+        };
+
+        // Messages without a timestamp, such as HRV, belong to the time of the one before.
+        let mut time = None;
+        self.data.retain(|message| {
+            let msg = match message {
+                FitMessage::Data(msg) => msg,
+                FitMessage::Definition(_) => return true,
+            };
+            if let Some(t) = get_field_time(253, &msg.data.values) {
+                time = Some(t);
+            }
+            match msg.data.message_type {
+                MessageType::Record
+                | MessageType::Event
+                | MessageType::Length
+                | MessageType::Hrv => time.is_none_or(|t| (start..=end).contains(&t)),
+                _ => true,
+            }
+        });
+
+        let positions: Vec<(f64, f64)> = self
+            .data
+            .iter()
+            .filter_map(|message| match message {
+                FitMessage::Data(msg) if msg.data.message_type == MessageType::Record => {
+                    get_position(0, 1, &msg.data.values)
+                }
+                _ => None,
+            })
+            .collect();
+        let (first_position, last_position) = (positions.first(), positions.last());
+        self.data.retain_mut(|message| match message {
+            FitMessage::Data(msg)
+                if matches!(
+                    msg.data.message_type,
+                    MessageType::Lap | MessageType::Session
+                ) =>
+            {
+                truncate_summary(msg, start, end, first_position, last_position)
+            }
+            _ => true,
+        });
+        self.number_laps();
+
+        let timer_events = self.timer_events();
+        if timer_events.first() != Some(&"start") {
+            let first = self.record_indexes().next();
+            if let Some(index) = first {
+                let mut builder = FitBuilder::new();
+                builder.push_timer_event(start, "start");
+                self.insert_messages(index, builder.build().data);
+            }
+        }
+        if !timer_events.last().is_some_and(|t| is_timer_stop(t)) {
+            let last = self.record_indexes().last();
+            if let Some(index) = last {
+                let mut builder = FitBuilder::new();
+                builder.push_timer_event(end, "stop_all");
+                self.insert_messages(index + 1, builder.build().data);
+            }
+        }
+
+        self.recompute_summaries();
+        self.update_activity(end);
+        Ok(())
+    }
+
+    /// Moves every date/time field of every message by `delta` seconds: timestamps, start
+    /// times, `time_created`, local timestamps and so on. Compressed timestamp headers are
+    /// rebased onto the shifted timestamps. Fails without changing anything if a time would
+    /// fall outside the FIT time range.
+    pub fn shift_time(&mut self, delta: i64) -> BinResult<()> {
+        let shift = |t: u32| {
+            (t as i64)
+                .checked_add(delta)
+                .and_then(|t| u32::try_from(t).ok())
+                .filter(|t| *t >= PSEUDO_EPOCH)
+                .ok_or_else(|| invalid_data("shifted time is outside the FIT time range"))
+        };
+        // Check every time first, so that a failure leaves the file as it was.
+        let mut last = None;
+        for msg in self.data_messages() {
+            for field in &msg.data.values {
+                if let Value::Time(t) = field.value {
+                    shift(t)?;
+                }
+            }
+            let time = msg
+                .timestamp()
+                .or_else(|| msg.header.compressed_time(last?));
+            if let Some(time) = time {
+                shift(time)?;
+                last = Some(time);
+            }
+        }
+
+        let mut last = None;
+        for message in &mut self.data {
+            if let FitMessage::Data(msg) = message {
+                let timestamp = msg.timestamp();
+                let time = timestamp.or_else(|| msg.header.compressed_time(last?));
+                if let (None, Some(time)) = (timestamp, time) {
+                    let shifted = shift(time)? as u8;
+                    msg.header.time_offset = Some(shifted & COMPRESSED_HEADER_TIME_OFFSET_MASK);
+                }
+                last = time.or(last);
+                for field in &mut msg.data.values {
+                    if let Value::Time(t) = field.value {
+                        field.value = Value::Time(shift(t)?);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the local timestamps to the message timestamp plus `offset` seconds, to fix the
+    /// time zone an activity was recorded in. Messages without a local timestamp get one.
+    /// Fails without changing anything if a local time would fall outside the FIT time range.
+    pub fn set_utc_offset(&mut self, offset: i32) -> BinResult<()> {
+        let local_field = |message_type| match message_type {
+            MessageType::Activity => Some(5),
+            MessageType::MonitoringInfo => Some(0),
+            MessageType::Monitoring => Some(11),
+            _ => None,
+        };
+        let local = |timestamp: u32| {
+            u32::try_from(timestamp as i64 + offset as i64)
+                .ok()
+                .filter(|t| *t >= PSEUDO_EPOCH)
+                .ok_or_else(|| invalid_data("local time is outside the FIT time range"))
+        };
+        for msg in self.data_messages() {
+            if let (Some(_), Some(timestamp)) =
+                (local_field(msg.data.message_type), msg.timestamp())
+            {
+                local(timestamp)?;
+            }
+        }
+
+        for message in &mut self.data {
+            if let FitMessage::Data(msg) = message {
+                let field_num = match local_field(msg.data.message_type) {
+                    Some(field_num) => field_num,
+                    None => continue,
+                };
+                if let Some(timestamp) = msg.timestamp() {
+                    put_field!(msg.data.values, field_num, Value::Time(local(timestamp)?));
+                }
+            }
+        }
+        self.define_added_fields();
+        Ok(())
+    }
+
+    /// Cuts the activity into consecutive parts starting at each of `timestamps`, every part
+    /// cropped like `Fit::crop` and stamped with its own `FileId` creation time. Parts
+    /// without records are left out.
+    pub fn split_at(&self, timestamps: &[u32]) -> BinResult<Vec<Fit>> {
+        let times: Vec<u32> = self
+            .record_indexes()
+            .filter_map(|index| match &self.data[index] {
+                FitMessage::Data(msg) => get_field_time(253, &msg.data.values),
+                FitMessage::Definition(_) => None,
+            })
+            .collect();
+        let (first, last) = match (times.iter().min(), times.iter().max()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return Err(invalid_data("file has no records to split")),
+        };
+        let mut starts: Vec<u32> = timestamps
+            .iter()
+            .copied()
+            .filter(|t| *t > first && *t <= last)
+            .collect();
+        starts.push(first);
+        starts.sort_unstable();
+        starts.dedup();
+
+        let mut parts = vec![];
+        for (i, start) in starts.iter().enumerate() {
+            let end = starts.get(i + 1).map_or(last, |next| next - 1);
+            if !times.iter().any(|t| (*start..=end).contains(t)) {
+                continue;
+            }
+            let mut part = self.clone();
+            part.crop(CropBound::Time(*start), CropBound::Time(end))?;
+            for message in &mut part.data {
+                if let FitMessage::Data(msg) = message {
+                    if msg.data.message_type == MessageType::FileId {
+                        update_field!(msg.data.values, 4, Value::Time(*start));
+                    }
+                }
+            }
+            part.header.data_size = 0;
+            parts.push(part);
+        }
+        Ok(parts)
+    }
+
+    /// Splits the activity into one file per `Lap`.
+    pub fn split_by_lap(&self) -> BinResult<Vec<Fit>> {
+        self.split_at(&self.start_times(MessageType::Lap))
+    }
+
+    /// Splits the activity into one file per `Session`.
+    pub fn split_by_session(&self) -> BinResult<Vec<Fit>> {
+        self.split_at(&self.start_times(MessageType::Session))
+    }
+
+    fn start_times(&self, message_type: MessageType) -> Vec<u32> {
+        self.data
+            .iter()
+            .filter_map(|message| match message {
+                FitMessage::Data(msg) if msg.data.message_type == message_type => {
+                    get_field_time(2, &msg.data.values)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn record_indexes(&self) -> impl DoubleEndedIterator<Item = usize> + '_ {
+        self.data
+            .iter()
+            .enumerate()
+            .filter_map(|(index, message)| match message {
+                FitMessage::Data(msg) if msg.data.message_type == MessageType::Record => {
+                    Some(index)
+                }
+                _ => None,
+            })
+    }
+
+    /// Renumbers the laps in file order and points every session at the laps it covers.
+    pub(crate) fn number_laps(&mut self) {
+        let mut laps = vec![];
+        for message in &mut self.data {
+            if let FitMessage::Data(msg) = message {
+                if msg.data.message_type == MessageType::Lap {
+                    update_field!(msg.data.values, 254, Value::U16(laps.len() as u16));
+                    laps.push(get_field_time(2, &msg.data.values));
+                }
+            }
+        }
+        for message in &mut self.data {
+            if let FitMessage::Data(msg) = message {
+                if msg.data.message_type != MessageType::Session {
+                    continue;
+                }
+                let values = &mut msg.data.values;
+                let (start, end) = match (get_field_time(2, values), get_field_time(253, values)) {
+                    (Some(start), Some(end)) => (start, end),
+                    _ => continue,
+                };
+                let covered = |lap: &Option<u32>| lap.is_some_and(|t| t >= start && t <= end);
+                let first = laps.iter().position(covered).unwrap_or_default();
+                let count = laps.iter().filter(|lap| covered(lap)).count();
+                set_field_scaled(MessageType::Session, 25, first as f64, values);
+                set_field_scaled(MessageType::Session, 26, count as f64, values);
+            }
+        }
+    }
+
+    /// Points the `Activity` at the current sessions: their count and total timer time, with
+    /// `end` as the timestamp.
+    pub(crate) fn update_activity(&mut self, end: u32) {
+        let mut num_sessions = 0;
+        let mut total_timer_time = 0.0;
+        for message in &self.data {
+            if let FitMessage::Data(msg) = message {
+                if msg.data.message_type == MessageType::Session {
+                    num_sessions += 1;
+                    total_timer_time += get_field_scaled(MessageType::Session, 8, &msg.data.values)
+                        .unwrap_or_default();
+                }
+            }
+        }
+        for message in &mut self.data {
+            if let FitMessage::Data(msg) = message {
+                if msg.data.message_type != MessageType::Activity {
+                    continue;
+                }
+                let values = &mut msg.data.values;
+                if let (Some(Value::Time(timestamp)), Some(Value::Time(local))) =
+                    (get_field_value(253, values), get_field_value(5, values))
+                {
+                    let local = local as i64 + end as i64 - timestamp as i64;
+                    update_field!(values, 5, Value::Time(local as u32));
+                }
+                update_field!(values, 253, Value::Time(end));
+                set_field_scaled(MessageType::Activity, 0, total_timer_time, values);
+                set_field_scaled(MessageType::Activity, 1, num_sessions as f64, values);
+            }
+        }
+    }
+}
+
+/// Clamps a lap or session to the window, returning `false` when it lies entirely outside.
+fn truncate_summary(
+    msg: &mut FitDataMessage,
+    start: u32,
+    end: u32,
+    first_position: Option<&(f64, f64)>,
+    last_position: Option<&(f64, f64)>,
+) -> bool {
+    let values = &mut msg.data.values;
+    let (lap_start, lap_end) = match (get_field_time(2, values), get_field_time(253, values)) {
+        (Some(lap_start), Some(lap_end)) => (lap_start, lap_end),
+        _ => return true,
+    };
+    // A lap ending exactly where the window starts only shares its boundary with it.
+    if lap_end < start || lap_start > end || (lap_end == start && lap_start < start) {
+        return false;
+    }
+    if lap_start < start {
+        update_field!(values, 2, Value::Time(start));
+        if let Some((lat, long)) = first_position {
+            update_field!(values, 3, Value::F32(*lat as f32));
+            update_field!(values, 4, Value::F32(*long as f32));
+        }
+    }
+    if lap_end > end {
+        update_field!(values, 253, Value::Time(end));
+        if let (MessageType::Lap, Some((lat, long))) = (msg.data.message_type, last_position) {
+            update_field!(values, 5, Value::F32(*lat as f32));
+            update_field!(values, 6, Value::F32(*long as f32));
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::{scaled_field, FitBuilder};
+    use crate::edit::CropBound;
+    use crate::protocol::data_field::DataField;
+    use crate::protocol::message_type::MessageType;
+    use crate::protocol::value::Value;
+    use crate::protocol::FitMessage;
+    use crate::test_util::{push_records, START};
+    use crate::Fit;
+
+    /// 100 s at 10 m/s with a lap every 50 s.
+    fn activity() -> Fit {
+        let mut builder = FitBuilder::new();
+        builder.push(
+            MessageType::FileId,
+            vec![
+                DataField::new(0, Value::Enum("activity")),
+                DataField::new(4, Value::Time(START)),
+            ],
+        );
+        builder.push_timer_event(START, "start");
+        for (from, to) in [(0, 50), (51, 100)] {
+            push_records(&mut builder, from..=to, |t| {
+                vec![scaled_field(MessageType::Record, 5, t as f64 * 10.0)]
+            });
+            builder.push(
+                MessageType::Lap,
+                vec![
+                    DataField::new(253, Value::Time(START + to)),
+                    DataField::new(2, Value::Time(START + to - 50)),
+                    scaled_field(MessageType::Lap, 8, 50.0),
+                    DataField::new(254, Value::U16(0)),
+                ],
+            );
+        }
+        builder.push_timer_event(START + 100, "stop_all");
+        builder.push(
+            MessageType::Session,
+            vec![
+                DataField::new(253, Value::Time(START + 100)),
+                DataField::new(2, Value::Time(START)),
+                scaled_field(MessageType::Session, 8, 100.0),
+                scaled_field(MessageType::Session, 9, 1000.0),
+                DataField::new(26, Value::U16(2)),
+            ],
+        );
+        builder.build()
+    }
+
+    #[test]
+    fn crop_test() {
+        let mut fit = activity();
+        fit.crop(CropBound::Distance(600.0), CropBound::Time(START + 80))
+            .unwrap();
+        let fit = Fit::read(fit.to_bytes().unwrap()).unwrap();
+
+        assert_eq!(fit.messages(MessageType::Record).count(), 21);
+        assert_eq!(fit.messages(MessageType::Event).count(), 2);
+        assert_eq!(fit.messages(MessageType::Lap).count(), 1);
+        let lap = fit.first(MessageType::Lap).unwrap();
+        assert_eq!(lap.get(2), Some(&Value::Time(START + 60)));
+        assert_eq!(lap.get(254), Some(&Value::U16(0)));
+        let session = fit.first(MessageType::Session).unwrap();
+        assert_eq!(session.get_f64(9), Some(200.0));
+        assert_eq!(session.get_f64(8), Some(20.0));
+        assert_eq!(session.get(26), Some(&Value::U16(1)));
+    }
+
+    #[test]
+    fn split_by_lap_test() {
+        let parts = activity().split_by_lap().unwrap();
+        assert_eq!(parts.len(), 2);
+        for (i, part) in parts.iter().enumerate() {
+            let part = Fit::read(part.to_bytes().unwrap()).unwrap();
+            let start = START + 50 * i as u32;
+            let file_id = part.first(MessageType::FileId).unwrap();
+            assert_eq!(file_id.get(4), Some(&Value::Time(start)));
+            assert_eq!(part.messages(MessageType::Lap).count(), 1);
+            let session = part.first(MessageType::Session).unwrap();
+            assert_eq!(session.get(2), Some(&Value::Time(start)));
+        }
+    }
+
+    #[test]
+    fn shift_time_test() {
+        let mut fit = activity();
+        // Give the second record a compressed timestamp header instead of a timestamp.
+        let record = fit
+            .data
+            .iter_mut()
+            .filter_map(|m| match m {
+                FitMessage::Data(msg) if msg.data.message_type == MessageType::Record => Some(msg),
+                _ => None,
+            })
+            .nth(1)
+            .unwrap();
+        record.header.compressed_header = true;
+        record.header.time_offset = Some(((START + 1) & 0x1F) as u8);
+        record.data.values[0].value = Value::None;
+
+        let delta = 86_400 + 7;
+        fit.shift_time(delta as i64).unwrap();
+        let file_id = fit.first(MessageType::FileId).unwrap();
+        assert_eq!(file_id.get(4), Some(&Value::Time(START + delta)));
+        let session = fit.first(MessageType::Session).unwrap();
+        assert_eq!(session.get(2), Some(&Value::Time(START + delta)));
+        assert_eq!(session.get(253), Some(&Value::Time(START + 100 + delta)));
+        let record = fit.messages(MessageType::Record).nth(1).unwrap();
+        assert_eq!(
+            record.header.compressed_time(START + delta),
+            Some(START + 1 + delta)
+        );
+
+        let before = fit.to_bytes().unwrap();
+        assert!(fit.shift_time(-(START as i64)).is_err());
+        assert_eq!(fit.to_bytes().unwrap(), before);
+    }
+
+    #[test]
+    fn set_utc_offset_test() {
+        let mut fit = activity();
+        let mut builder = FitBuilder::new();
+        builder.push(
+            MessageType::Activity,
+            vec![DataField::new(253, Value::Time(START + 100))],
+        );
+        fit.data.extend(builder.build().data);
+
+        assert!(fit.set_utc_offset(-(START as i32)).is_err());
+        fit.set_utc_offset(8 * 3600).unwrap();
+        let fit = Fit::read(fit.to_bytes().unwrap()).unwrap();
+        let activity = fit.first(MessageType::Activity).unwrap();
+        assert_eq!(activity.get(5), Some(&Value::Time(START + 100 + 8 * 3600)));
+        assert_eq!(fit.messages(MessageType::Record).count(), 101);
+    }
+}
@@ -0,0 +1,10 @@
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// Great-circle distance in metres between two (latitude, longitude) pairs in degrees.
+pub(crate) fn haversine(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (lat1, lat2) = (a.0.to_radians(), b.0.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_long = (b.1 - a.1).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_long / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * h.sqrt().asin()
+}
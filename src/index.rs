@@ -0,0 +1,171 @@
+use crate::lazy::{read_layout, LazyMessage, LazyOptions};
+use crate::protocol::consts::DEVELOPER_FIELDS_MASK;
+use crate::protocol::io::invalid_data;
+use crate::protocol::message_type::MessageType;
+use crate::protocol::{FitDataMessage, FitMessageHeader};
+use crate::Fit;
+use binrw::{BinReaderExt, BinResult};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+/// Where the data messages of a file are, so that any of them can be read without going
+/// through the messages before it.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FitIndex {
+    pub entries: Vec<IndexEntry>,
+}
+
+/// One data message of an indexed file.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IndexEntry {
+    pub global_message_number: u16,
+    /// Unix time of the message, from its `timestamp` field or compressed header.
+    pub timestamp: Option<u32>,
+    /// Position of the message header in the file.
+    pub offset: u64,
+    /// Position of the definition message the message is read with.
+    pub definition_offset: u64,
+}
+
+impl Fit {
+    /// Indexes the data messages of a file, decoding only their timestamps.
+    ///
+    /// Messages with a global message number unknown to the profile are not indexed, just
+    /// as `Fit::read` and `Fit::read_lazy` leave them out. Their timestamps are not seen
+    /// either, so a compressed timestamp after one resolves against the message before it.
+    pub fn index(buf: &[u8]) -> BinResult<FitIndex> {
+        let lazy = Fit::read_lazy(buf, &LazyOptions::default())?;
+        let mut last = None;
+        let entries = lazy
+            .messages
+            .iter()
+            .map(|msg| {
+                let timestamp = msg
+                    .timestamp()
+                    .or_else(|| msg.header.compressed_time(last?));
+                last = timestamp.or(last);
+                IndexEntry {
+                    global_message_number: msg.message_type.to_primitive(),
+                    timestamp,
+                    offset: msg.offset as u64,
+                    definition_offset: msg.definition_offset as u64,
+                }
+            })
+            .collect();
+        Ok(FitIndex { entries })
+    }
+}
+
+impl FitIndex {
+    /// The entries of one message type, in file order.
+    pub fn messages(&self, message_type: MessageType) -> impl Iterator<Item = &IndexEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.message_type() == message_type)
+    }
+
+    pub fn last(&self, message_type: MessageType) -> Option<&IndexEntry> {
+        self.messages(message_type).last()
+    }
+
+    /// The entries with a timestamp within `start..=end`, as unix times.
+    pub fn between(&self, start: u32, end: u32) -> impl Iterator<Item = &IndexEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.timestamp.is_some_and(|t| (start..=end).contains(&t)))
+    }
+}
+
+impl IndexEntry {
+    pub fn message_type(&self) -> MessageType {
+        MessageType::from_primitive(self.global_message_number).unwrap_or(MessageType::None)
+    }
+
+    /// Seeks to the message and its definition in the indexed file and decodes it.
+    pub fn read<R: Read + Seek>(&self, reader: &mut R) -> BinResult<FitDataMessage> {
+        reader.seek(SeekFrom::Start(self.definition_offset))?;
+        let mut definition = vec![0; 6];
+        reader.read_exact(&mut definition)?;
+        let definition_header: FitMessageHeader = Cursor::new(&definition).read_ne()?;
+        let dev_fields = definition[0] & DEVELOPER_FIELDS_MASK != 0;
+        let num_fields = definition[5] as usize;
+        read_more(reader, &mut definition, num_fields * 3)?;
+        if dev_fields {
+            read_more(reader, &mut definition, 1)?;
+            let count = definition[definition.len() - 1] as usize;
+            read_more(reader, &mut definition, count * 3)?;
+        }
+        let layout = read_layout(&definition, 0, dev_fields)?;
+
+        reader.seek(SeekFrom::Start(self.offset))?;
+        let mut message = vec![0; 1 + layout.size];
+        reader.read_exact(&mut message)?;
+        let header: FitMessageHeader = Cursor::new(&message).read_ne()?;
+        if !definition_header.definition
+            || header.definition
+            || header.local_num != definition_header.local_num
+        {
+            return Err(invalid_data("the index does not match the file"));
+        }
+        let msg = LazyMessage::new(header, self.offset as usize, layout, &message[1..]);
+        Ok(msg.decode())
+    }
+}
+
+fn read_more<R: Read>(reader: &mut R, buf: &mut Vec<u8>, count: usize) -> BinResult<()> {
+    let start = buf.len();
+    buf.resize(start + count, 0);
+    reader.read_exact(&mut buf[start..])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::data_field::DataField;
+    use crate::protocol::message_type::MessageType;
+    use crate::protocol::value::Value;
+    use crate::test_util::{heart_rate, recording};
+    use crate::Fit;
+    use std::io::Cursor;
+
+    #[test]
+    fn index_test() {
+        let mut builder = recording(0..5, heart_rate);
+        builder.push(
+            MessageType::Session,
+            vec![
+                DataField::new(253, Value::Time(1_000_000_005)),
+                DataField::new(9, Value::U32(123_400)),
+            ],
+        );
+        let buf = builder.build().to_bytes().unwrap();
+        let fit = Fit::read(buf.clone()).unwrap();
+        let index = Fit::index(&buf).unwrap();
+        assert_eq!(index.entries.len(), 7);
+
+        let mut reader = Cursor::new(&buf);
+        let session = index.last(MessageType::Session).unwrap();
+        assert_eq!(
+            &session.read(&mut reader).unwrap(),
+            fit.first(MessageType::Session).unwrap()
+        );
+        let heart_rates: Vec<_> = index
+            .between(1_000_000_002, 1_000_000_003)
+            .map(|entry| entry.read(&mut reader).unwrap().get(3).cloned())
+            .collect();
+        assert_eq!(
+            heart_rates,
+            vec![Some(Value::U8(142)), Some(Value::U8(143))]
+        );
+
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_string(&index).unwrap();
+            assert_eq!(
+                serde_json::from_str::<crate::index::FitIndex>(&json).unwrap(),
+                index
+            );
+        }
+    }
+}
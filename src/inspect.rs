@@ -0,0 +1,275 @@
+use crate::protocol::base_type::base_type_name;
+use crate::protocol::get_field_name::get_field_name;
+use crate::protocol::message_type::MessageType;
+use crate::protocol::{
+    calculate_fit_crc, FitDefinitionMessage, FitHeader, FitMessage, FitMessageHeader,
+};
+use crate::repair::read_message;
+use crate::Fit;
+use binrw::{BinReaderExt, Endian, Error};
+use std::fmt::Write;
+use std::io::Cursor;
+
+/// Bytes shown per line; longer runs continue on the following lines.
+const BYTES_PER_LINE: usize = 16;
+/// Bytes dumped after the point where decoding failed.
+const TRAILING_BYTES: usize = 256;
+
+impl Fit {
+    /// Walks the raw bytes of a file and describes each of them: the file header, every
+    /// message header with its flags and local number, the layout of each definition and
+    /// the bytes of every field next to the value decoded from them.
+    ///
+    /// Decoding stops at the first message that cannot be read. The line starting with `!!`
+    /// tells why, followed by the bytes from that point on.
+    pub fn inspect(buf: &[u8]) -> String {
+        let mut out = String::new();
+        let mut cursor = Cursor::new(buf);
+        let header: FitHeader = match cursor.read_ne() {
+            Ok(header) => header,
+            Err(e) => {
+                diverged(&mut out, buf, 0, &reason(&e));
+                return out;
+            }
+        };
+        let header_size = header.header_size as usize;
+        let mut annotation = format!(
+            "header size {}, protocol {}.{}, profile {}.{:02}, data size {}, {}",
+            header_size,
+            header.protocol_version >> 4,
+            header.protocol_version & 0x0F,
+            header.profile_version / 100,
+            header.profile_version % 100,
+            header.data_size,
+            header.data_type
+        );
+        if let (Some(crc), true) = (header.crc, header_size >= 14) {
+            let expected = calculate_fit_crc(&buf[..12]);
+            let _ = write!(annotation, ", crc {:#06x}", crc);
+            if crc != 0 && crc != expected {
+                let _ = write!(annotation, " (expected {:#06x})", expected);
+            }
+        }
+        line(&mut out, buf, 0, header_size.min(buf.len()), &annotation);
+
+        let end = header_size + header.data_size as usize;
+        let mut definitions: Vec<FitDefinitionMessage> = vec![];
+        let mut position = header_size;
+        cursor.set_position(position as u64);
+        while position < end {
+            let message_header = cursor.read_ne::<FitMessageHeader>().ok();
+            cursor.set_position(position as u64);
+            let message = match read_message(&mut cursor, &definitions) {
+                Ok(message) => message,
+                Err(e) => {
+                    diverged(&mut out, buf, position, &reason(&e));
+                    return out;
+                }
+            };
+            let next = cursor.position() as usize;
+            match (message_header, message) {
+                (_, Some(FitMessage::Definition(def))) => {
+                    definition(&mut out, buf, position, &def);
+                    definitions.retain(|d| d.header.local_num != def.header.local_num);
+                    definitions.push(def);
+                }
+                (_, Some(FitMessage::Data(msg))) => {
+                    let annotation =
+                        format!("{}: {:?}", describe(&msg.header), msg.data.message_type);
+                    line(&mut out, buf, position, position + 1, &annotation);
+                    let def = definitions
+                        .iter()
+                        .find(|def| def.header.local_num == msg.header.local_num);
+                    let mut offset = position + 1;
+                    for (field, value) in def
+                        .into_iter()
+                        .flat_map(|def| def.data.fields.iter())
+                        .zip(&msg.data.values)
+                    {
+                        let size = field.size as usize;
+                        let name = get_field_name(msg.data.message_type, field.definition_number)
+                            .map(|name| format!(" {}", name))
+                            .unwrap_or_default();
+                        let annotation = format!(
+                            "field {}{} = {:?}",
+                            field.definition_number, name, value.value
+                        );
+                        line(&mut out, buf, offset, offset + size, &annotation);
+                        offset += size;
+                    }
+                }
+                (Some(message_header), None) => {
+                    let global = definitions
+                        .iter()
+                        .find(|def| def.header.local_num == message_header.local_num)
+                        .map(|def| def.data.global_message_number);
+                    let annotation = format!(
+                        "{}: unknown global message {}, skipped",
+                        describe(&message_header),
+                        global.unwrap_or_default()
+                    );
+                    line(&mut out, buf, position, position + 1, &annotation);
+                    line(&mut out, buf, position + 1, next, "");
+                }
+                (None, None) => {}
+            }
+            position = next;
+        }
+        if position > end {
+            let _ = writeln!(
+                out,
+                "!! the last message ends {} bytes past the declared data size",
+                position - end
+            );
+        }
+
+        if buf.len() >= end + 2 {
+            let crc = u16::from_le_bytes([buf[end], buf[end + 1]]);
+            let expected = calculate_fit_crc(&buf[header_size..end]);
+            let mut annotation = format!("crc {:#06x}", crc);
+            if crc != expected && crc != calculate_fit_crc(&buf[..end]) {
+                let _ = write!(annotation, " (expected {:#06x})", expected);
+            }
+            line(&mut out, buf, end, end + 2, &annotation);
+            if buf.len() > end + 2 {
+                diverged(&mut out, buf, end + 2, "bytes after the file crc");
+            }
+        } else {
+            diverged(&mut out, buf, end.min(buf.len()), "missing file crc");
+        }
+        out
+    }
+}
+
+/// The cause of a read error, without the backtrace binrw formats around it.
+fn reason(e: &Error) -> String {
+    match e {
+        Error::Backtrace(backtrace) => reason(&backtrace.error),
+        e => e.to_string(),
+    }
+}
+
+fn describe(header: &FitMessageHeader) -> String {
+    let mut out = match (header.compressed_header, header.definition) {
+        (true, _) => format!(
+            "compressed timestamp, local {}, time offset {}",
+            header.local_num,
+            header.time_offset.unwrap_or_default()
+        ),
+        (false, true) => format!("definition, local {}", header.local_num),
+        (false, false) => format!("data, local {}", header.local_num),
+    };
+    if header.dev_fields {
+        out.push_str(", developer fields");
+    }
+    out
+}
+
+fn definition(out: &mut String, buf: &[u8], position: usize, def: &FitDefinitionMessage) {
+    line(out, buf, position, position + 1, &describe(&def.header));
+    let message_type = MessageType::from_primitive(def.data.global_message_number);
+    let endian = match def.data.endian {
+        Endian::Little => "little endian",
+        Endian::Big => "big endian",
+    };
+    let annotation = format!(
+        "{}, global {} ({:?}), {} fields",
+        endian,
+        def.data.global_message_number,
+        message_type.unwrap_or(MessageType::None),
+        def.data.num_fields
+    );
+    line(out, buf, position + 1, position + 6, &annotation);
+    let mut offset = position + 6;
+    for field in &def.data.fields {
+        let name = message_type
+            .and_then(|message_type| get_field_name(message_type, field.definition_number))
+            .map(|name| format!(" {}", name))
+            .unwrap_or_default();
+        let annotation = format!(
+            "field {}{}: size {}, {}",
+            field.definition_number,
+            name,
+            field.size,
+            base_type_name(field.base_type.val)
+        );
+        line(out, buf, offset, offset + 3, &annotation);
+        offset += 3;
+    }
+}
+
+/// Writes `buf[start..end]` in hex next to its annotation, at most `BYTES_PER_LINE` bytes
+/// to a line.
+fn line(out: &mut String, buf: &[u8], start: usize, end: usize, annotation: &str) {
+    let end = end.min(buf.len());
+    let mut offset = start;
+    let mut annotation = Some(annotation);
+    loop {
+        let chunk_end = end.min(offset + BYTES_PER_LINE);
+        let hex: Vec<String> = buf[offset..chunk_end]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        let text = annotation.take().unwrap_or_default();
+        let line = format!(
+            "{:08x}  {:<width$}  {}",
+            offset,
+            hex.join(" "),
+            text,
+            width = BYTES_PER_LINE * 3 - 1
+        );
+        let _ = writeln!(out, "{}", line.trim_end());
+        offset = chunk_end;
+        if offset >= end {
+            break;
+        }
+    }
+}
+
+/// Reports where decoding stopped and dumps the bytes from there on.
+fn diverged(out: &mut String, buf: &[u8], position: usize, reason: &str) {
+    let _ = writeln!(out, "!! {:08x}: {}", position, reason);
+    let end = buf.len().min(position + TRAILING_BYTES);
+    if position < end {
+        line(out, buf, position, end, "");
+    }
+    if buf.len() > end {
+        let _ = writeln!(out, "!! {} more bytes", buf.len() - end);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::FitBuilder;
+    use crate::protocol::data_field::DataField;
+    use crate::protocol::message_type::MessageType;
+    use crate::protocol::value::Value;
+    use crate::Fit;
+
+    #[test]
+    fn inspect_test() {
+        let mut builder = FitBuilder::new();
+        builder.push(
+            MessageType::Record,
+            vec![
+                DataField::new(253, Value::Time(1_000_000_000)),
+                DataField::new(3, Value::U8(150)),
+            ],
+        );
+        let buf = builder.build().to_bytes().unwrap();
+        let out = Fit::inspect(&buf);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 9);
+        assert!(lines[1].ends_with("definition, local 0"));
+        assert!(lines[2].ends_with("little endian, global 20 (Record), 2 fields"));
+        assert!(lines[3].ends_with("field 253 timestamp: size 4, uint32"));
+        assert!(lines[5].ends_with("data, local 0: Record"));
+        assert!(lines[7].contains(" 96 ") && lines[7].ends_with("field 3 heart_rate = U8(150)"));
+        assert!(lines[8].ends_with("crc 0x56ab"));
+        assert!(!out.contains("!!"));
+
+        // Cut the file inside the data message.
+        let out = Fit::inspect(&buf[..buf.len() - 4]);
+        assert!(out.contains("!! 0000001a: partial data message"));
+    }
+}
@@ -0,0 +1,267 @@
+use crate::builder::FitBuilder;
+use crate::protocol::data_field::DataField;
+use crate::protocol::io::invalid_data;
+use crate::protocol::macros::{get_field_scaled, get_field_time, get_field_value};
+use crate::protocol::message_type::MessageType;
+use crate::protocol::value::Value;
+use crate::protocol::FieldDefinition;
+use crate::Fit;
+use binrw::BinResult;
+use std::collections::HashMap;
+use std::slice::from_ref;
+
+/// Settings for `Fit::merge_interleaved`.
+#[derive(Debug, Clone, Default)]
+pub struct InterleaveOptions {
+    /// Records of different inputs at most this many seconds apart are fused into one.
+    pub tolerance: u32,
+    /// Input indexes in order of preference, per `Record` field number. Inputs not listed,
+    /// and fields without an entry, fall back to input order.
+    pub priorities: HashMap<u8, Vec<usize>>,
+}
+
+impl InterleaveOptions {
+    /// Prefers `sources`, in order, for the `Record` field `field_num`.
+    pub fn prefer(mut self, field_num: u8, sources: Vec<usize>) -> Self {
+        self.priorities.insert(field_num, sources);
+        self
+    }
+
+    /// The inputs to take `field_num` from, most preferred first. Longitude follows the
+    /// latitude preference unless it has its own.
+    fn ranking(&self, field_num: u8, inputs: usize) -> Vec<usize> {
+        let preferred = self.priorities.get(&field_num).or_else(|| match field_num {
+            1 => self.priorities.get(&0),
+            _ => None,
+        });
+        let mut ranking: Vec<usize> = preferred
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(|source| *source < inputs)
+            .collect();
+        for source in 0..inputs {
+            if !ranking.contains(&source) {
+                ranking.push(source);
+            }
+        }
+        ranking
+    }
+}
+
+struct Entry {
+    time: u32,
+    order: u8,
+    message_type: MessageType,
+    fields: Vec<(FieldDefinition, DataField)>,
+}
+
+struct Record {
+    time: u32,
+    source: usize,
+    fields: Vec<(FieldDefinition, DataField)>,
+}
+
+impl Fit {
+    /// Merges recordings of the same activity made by several devices at once.
+    ///
+    /// All messages are interleaved by timestamp. `Record`s of different inputs that fall
+    /// within `options.tolerance` seconds are fused into one record, taking each field from
+    /// the most preferred input that has a valid value. `FileId`, timer events, laps,
+    /// sessions and the activity come from the first input only; their summaries are then
+    /// recomputed from the fused records.
+    pub fn merge_interleaved(fits: Vec<Fit>, options: &InterleaveOptions) -> BinResult<Fit> {
+        let header = match fits.first() {
+            Some(fit) => fit.header.clone(),
+            None => return Err(invalid_data("no files to merge")),
+        };
+        let mut entries = vec![];
+        let mut records = vec![];
+        for (source, fit) in fits.iter().enumerate() {
+            // Messages without a timestamp stay behind the message before them.
+            let mut time = 0;
+            for (def, msg) in fit.defined_messages() {
+                let values = &msg.data.values;
+                if let Some(t) = get_field_time(253, values) {
+                    time = t;
+                }
+                let fields = def
+                    .data
+                    .fields
+                    .iter()
+                    .copied()
+                    .zip(values.clone())
+                    .collect();
+                let order = match msg.data.message_type {
+                    MessageType::Record => {
+                        records.push(Record {
+                            time,
+                            source,
+                            fields,
+                        });
+                        continue;
+                    }
+                    MessageType::FileId
+                    | MessageType::FileCreator
+                    | MessageType::Lap
+                    | MessageType::Session
+                    | MessageType::Activity
+                        if source > 0 =>
+                    {
+                        continue
+                    }
+                    MessageType::Event
+                        if source > 0
+                            && get_field_value(0, values) == Some(Value::Enum("timer")) =>
+                    {
+                        continue
+                    }
+                    MessageType::Lap | MessageType::Session | MessageType::Activity => 2,
+                    _ => 0,
+                };
+                entries.push(Entry {
+                    time,
+                    order,
+                    message_type: msg.data.message_type,
+                    fields,
+                });
+            }
+        }
+
+        records.sort_by_key(|record| (record.time, record.source));
+        let mut records = records.into_iter().peekable();
+        while let Some(first) = records.next() {
+            let time = first.time;
+            let mut group = vec![first];
+            while let Some(next) = records.peek() {
+                if next.time > time.saturating_add(options.tolerance)
+                    || group.iter().any(|record| record.source == next.source)
+                {
+                    break;
+                }
+                group.extend(records.next());
+            }
+            entries.push(Entry {
+                time,
+                order: 1,
+                message_type: MessageType::Record,
+                fields: fuse_records(time, group, options, fits.len()),
+            });
+        }
+        entries.sort_by_key(|entry| (entry.time, entry.order));
+
+        let mut builder = FitBuilder::new();
+        for entry in entries {
+            let (fields, values) = entry.fields.into_iter().unzip();
+            builder.push_defined(entry.message_type, fields, values);
+        }
+        let mut fit = builder.build();
+        fit.header = header;
+        fit.recompute_summaries();
+        Ok(fit)
+    }
+}
+
+fn fuse_records(
+    time: u32,
+    group: Vec<Record>,
+    options: &InterleaveOptions,
+    inputs: usize,
+) -> Vec<(FieldDefinition, DataField)> {
+    let mut field_nums: Vec<u8> = vec![];
+    for record in &group {
+        for (_, field) in &record.fields {
+            if !field_nums.contains(&field.field_num) {
+                field_nums.push(field.field_num);
+            }
+        }
+    }
+    let mut fused = vec![];
+    for field_num in field_nums {
+        let candidates = options
+            .ranking(field_num, inputs)
+            .into_iter()
+            .filter_map(|source| group.iter().find(|record| record.source == source))
+            .filter_map(|record| record.fields.iter().find(|(_, f)| f.field_num == field_num));
+        let mut chosen = None;
+        for candidate in candidates {
+            chosen.get_or_insert(candidate);
+            if is_valid(&candidate.1) {
+                chosen = Some(candidate);
+                break;
+            }
+        }
+        if let Some((def, field)) = chosen {
+            let mut field = field.clone();
+            if field_num == 253 {
+                field.value = Value::Time(time);
+            }
+            fused.push((*def, field));
+        }
+    }
+    fused
+}
+
+fn is_valid(field: &DataField) -> bool {
+    match field.value {
+        Value::None => false,
+        // Unset coordinates decode to 180°.
+        Value::F32(v) if field.field_num <= 1 => v.abs() < 180.0,
+        Value::Enum(_)
+        | Value::String(_)
+        | Value::Time(_)
+        | Value::ArrU8(_)
+        | Value::ArrU16(_)
+        | Value::ArrU32(_) => true,
+        _ => get_field_scaled(MessageType::Record, field.field_num, from_ref(field)).is_some(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interleave::InterleaveOptions;
+    use crate::protocol::data_field::DataField;
+    use crate::protocol::message_type::MessageType;
+    use crate::protocol::value::Value;
+    use crate::test_util::{activity_builder, push_records, START};
+    use crate::Fit;
+
+    fn recording(start: u32, heart_rate: u8, power: u16) -> Fit {
+        let mut builder = activity_builder();
+        builder.push_timer_event(START + start, "start");
+        push_records(&mut builder, start..start + 3, |_| {
+            vec![
+                DataField::new(3, Value::U8(heart_rate)),
+                DataField::new(7, Value::U16(power)),
+            ]
+        });
+        builder.build()
+    }
+
+    #[test]
+    fn merge_interleaved_test() {
+        let watch = recording(0, 150, u16::MAX);
+        let head_unit = recording(1, 140, 250);
+        let options = InterleaveOptions::default().prefer(3, vec![1, 0]);
+        let fit = Fit::merge_interleaved(vec![watch, head_unit], &options).unwrap();
+        let fit = Fit::read(fit.to_bytes().unwrap()).unwrap();
+
+        let records: Vec<_> = fit.messages(MessageType::Record).collect();
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[0].get(3), Some(&Value::U8(150)));
+        assert_eq!(records[0].get(7), Some(&Value::U16(u16::MAX)));
+        assert_eq!(records[1].get(3), Some(&Value::U8(140)));
+        assert_eq!(records[1].get(7), Some(&Value::U16(250)));
+        assert_eq!(records[3].get(3), Some(&Value::U8(140)));
+
+        // A record never fuses with a second one of the same input, however large the tolerance.
+        let options = InterleaveOptions {
+            tolerance: u32::MAX,
+            ..InterleaveOptions::default()
+        };
+        let watch = recording(0, 150, u16::MAX);
+        let head_unit = recording(1, 140, 250);
+        let fit = Fit::merge_interleaved(vec![watch, head_unit], &options).unwrap();
+        assert_eq!(fit.messages(MessageType::Record).count(), 4);
+    }
+}
@@ -0,0 +1,349 @@
+use crate::protocol::consts::{FIELD_DEFINITION_BASE_ENDIAN, FIELD_DEFINITION_BASE_NUMBER};
+use crate::protocol::data_field::DataField;
+use crate::protocol::get_field_offset::get_field_offset_fn;
+use crate::protocol::get_field_scale::get_field_scale_fn;
+use crate::protocol::get_field_type::get_field_type_fn;
+use crate::protocol::io::invalid_data;
+use crate::protocol::macros::get_field_time;
+use crate::protocol::message_type::{parse_message_type, MessageType};
+use crate::protocol::value::Value;
+use crate::protocol::{
+    DataMessage, FieldDefBaseType, FieldDefinition, FitDataMessage, FitHeader, FitMessageHeader,
+};
+use crate::Fit;
+use alloc::vec;
+use alloc::vec::Vec;
+use binrw::io::Cursor;
+use binrw::{BinReaderExt, BinResult, Endian};
+
+const LOCAL_MESSAGE_COUNT: usize = 16;
+
+/// Settings for `Fit::read_lazy`.
+#[derive(Debug, Clone, Default)]
+pub struct LazyOptions {
+    /// Message types passed over without being recorded, e.g. `Record` and `Hrv` when only
+    /// the summary messages are of interest.
+    pub skip: Vec<MessageType>,
+}
+
+/// A file scanned by `Fit::read_lazy`: where each data message is, with its bytes borrowed
+/// from the scanned buffer.
+#[derive(Debug, Clone)]
+pub struct LazyFit<'a> {
+    pub header: FitHeader,
+
+    pub messages: Vec<LazyMessage<'a>>,
+    /// Positions of the definition messages in the file.
+    pub definitions: Vec<usize>,
+}
+
+/// A data message whose fields are decoded when they are asked for.
+#[derive(Debug, Clone)]
+pub struct LazyMessage<'a> {
+    pub header: FitMessageHeader,
+    pub message_type: MessageType,
+    /// Position of the message header in the file.
+    pub offset: usize,
+    /// Position of the definition message the message was read with.
+    pub definition_offset: usize,
+    endian: Endian,
+    /// The field definitions from the definition message, three bytes each.
+    fields: &'a [u8],
+    /// The field bytes, developer fields included.
+    bytes: &'a [u8],
+}
+
+/// What a definition message says about the data messages that follow it.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Layout<'a> {
+    offset: usize,
+    message_type: MessageType,
+    endian: Endian,
+    fields: &'a [u8],
+    /// Bytes taken by a data message after its header.
+    pub(crate) size: usize,
+}
+
+/// A `Layout` keeping its own copy of the field definitions, for readers that don't hold on
+/// to the definition message bytes.
+#[cfg(feature = "async")]
+#[derive(Debug, Clone)]
+pub(crate) struct OwnedLayout {
+    offset: usize,
+    message_type: MessageType,
+    endian: Endian,
+    fields: Vec<u8>,
+    pub(crate) size: usize,
+}
+
+#[cfg(feature = "async")]
+impl Layout<'_> {
+    pub(crate) fn to_owned(self) -> OwnedLayout {
+        OwnedLayout {
+            offset: self.offset,
+            message_type: self.message_type,
+            endian: self.endian,
+            fields: self.fields.to_vec(),
+            size: self.size,
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl OwnedLayout {
+    pub(crate) fn as_layout(&self) -> Layout<'_> {
+        Layout {
+            offset: self.offset,
+            message_type: self.message_type,
+            endian: self.endian,
+            fields: &self.fields,
+            size: self.size,
+        }
+    }
+}
+
+impl Fit {
+    /// Scans a file without decoding it: only the message boundaries and definitions are
+    /// read, and each data message keeps a slice of `buf` that is decoded on access.
+    ///
+    /// Messages of the types in `options.skip` are stepped over. Unlike `Fit::read`, data
+    /// messages with developer fields are accepted; the developer fields are not decoded.
+    pub fn read_lazy<'a>(buf: &'a [u8], options: &LazyOptions) -> BinResult<LazyFit<'a>> {
+        let mut cursor = Cursor::new(buf);
+        let header: FitHeader = cursor.read_ne()?;
+        let end = header.header_size as usize + header.data_size as usize;
+        let data = buf
+            .get(..end)
+            .ok_or_else(|| invalid_data("file is shorter than its data size"))?;
+
+        let mut layouts: [Option<Layout>; LOCAL_MESSAGE_COUNT] = [None; LOCAL_MESSAGE_COUNT];
+        let mut messages = vec![];
+        let mut definitions = vec![];
+        let mut position = header.header_size as usize;
+        while position < end {
+            let offset = position;
+            cursor.set_position(position as u64);
+            let message_header: FitMessageHeader = cursor.read_ne()?;
+            let local = message_header.local_num as usize;
+            if message_header.definition {
+                let layout = read_layout(data, offset, message_header.dev_fields)?;
+                definitions.push(offset);
+                position = offset + 6 + layout.fields.len();
+                if message_header.dev_fields {
+                    position += 1 + data[position] as usize * 3;
+                }
+                layouts[local] = Some(layout);
+            } else {
+                let layout = layouts[local]
+                    .ok_or_else(|| invalid_data("data message without a definition"))?;
+                let bytes = data
+                    .get(offset + 1..offset + 1 + layout.size)
+                    .ok_or_else(|| invalid_data("partial data message"))?;
+                position = offset + 1 + layout.size;
+                if layout.message_type == MessageType::None
+                    || options.skip.contains(&layout.message_type)
+                {
+                    continue;
+                }
+                messages.push(LazyMessage::new(message_header, offset, layout, bytes));
+            }
+        }
+        Ok(LazyFit {
+            header,
+            messages,
+            definitions,
+        })
+    }
+}
+
+/// Reads the definition message whose header is at `data[offset]`.
+pub(crate) fn read_layout(data: &[u8], offset: usize, dev_fields: bool) -> BinResult<Layout<'_>> {
+    let partial = || invalid_data("partial definition message");
+    let fixed = data.get(offset + 1..offset + 6).ok_or_else(partial)?;
+    let endian = match fixed[1] {
+        0 => Endian::Little,
+        _ => Endian::Big,
+    };
+    let global = match endian {
+        Endian::Little => u16::from_le_bytes([fixed[2], fixed[3]]),
+        Endian::Big => u16::from_be_bytes([fixed[2], fixed[3]]),
+    };
+    let mut position = offset + 6;
+    let fields = data
+        .get(position..position + fixed[4] as usize * 3)
+        .ok_or_else(partial)?;
+    position += fields.len();
+    let mut size = total_size(fields);
+    if dev_fields {
+        let count = *data.get(position).ok_or_else(partial)? as usize;
+        let dev_fields = data
+            .get(position + 1..position + 1 + count * 3)
+            .ok_or_else(partial)?;
+        size += total_size(dev_fields);
+    }
+    Ok(Layout {
+        offset,
+        message_type: parse_message_type(&mut Cursor::new(fixed), endian, (global,))?,
+        endian,
+        fields,
+        size,
+    })
+}
+
+/// The bytes taken by the fields of a list of three byte field definitions.
+fn total_size(fields: &[u8]) -> usize {
+    fields.chunks_exact(3).map(|field| field[1] as usize).sum()
+}
+
+impl<'a> LazyFit<'a> {
+    /// The messages of one type, in file order.
+    pub fn messages(&self, message_type: MessageType) -> impl Iterator<Item = &LazyMessage<'a>> {
+        self.messages
+            .iter()
+            .filter(move |msg| msg.message_type == message_type)
+    }
+
+    pub fn first(&self, message_type: MessageType) -> Option<&LazyMessage<'a>> {
+        self.messages(message_type).next()
+    }
+}
+
+impl<'a> LazyMessage<'a> {
+    pub(crate) fn new(
+        header: FitMessageHeader,
+        offset: usize,
+        layout: Layout<'a>,
+        bytes: &'a [u8],
+    ) -> Self {
+        LazyMessage {
+            header,
+            message_type: layout.message_type,
+            offset,
+            definition_offset: layout.offset,
+            endian: layout.endian,
+            fields: layout.fields,
+            bytes,
+        }
+    }
+
+    /// The layout of the message, as given by its definition.
+    pub fn field_definitions(&self) -> impl Iterator<Item = FieldDefinition> + 'a {
+        self.fields.chunks_exact(3).map(|field| FieldDefinition {
+            definition_number: field[0],
+            size: field[1],
+            base_type: FieldDefBaseType::new(
+                field[2] & FIELD_DEFINITION_BASE_ENDIAN != 0,
+                field[2] & FIELD_DEFINITION_BASE_NUMBER,
+            ),
+        })
+    }
+
+    /// The undecoded bytes of a field.
+    pub fn raw(&self, field_num: u8) -> Option<&'a [u8]> {
+        self.locate(field_num).map(|(_, bytes)| bytes)
+    }
+
+    /// Decodes a single field the way `Fit::read` does.
+    pub fn get(&self, field_num: u8) -> Option<Value> {
+        let (definition, bytes) = self.locate(field_num)?;
+        Some(self.decode_field(&definition, bytes).value)
+    }
+
+    /// The `timestamp` field as a unix time.
+    pub fn timestamp(&self) -> Option<u32> {
+        let (definition, bytes) = self.locate(253)?;
+        get_field_time(253, &[self.decode_field(&definition, bytes)])
+    }
+
+    /// Decodes every field, giving the message `Fit::read` would have produced.
+    pub fn decode(&self) -> FitDataMessage {
+        let mut offset = 0;
+        let values = self
+            .field_definitions()
+            .map(|definition| {
+                let size = definition.size as usize;
+                let field = self.decode_field(&definition, &self.bytes[offset..offset + size]);
+                offset += size;
+                field
+            })
+            .collect();
+        FitDataMessage {
+            header: self.header.clone(),
+            data: DataMessage {
+                message_type: self.message_type,
+                values,
+            },
+        }
+    }
+
+    fn locate(&self, field_num: u8) -> Option<(FieldDefinition, &'a [u8])> {
+        let mut offset = 0;
+        for definition in self.field_definitions() {
+            let size = definition.size as usize;
+            if definition.definition_number == field_num {
+                return Some((definition, &self.bytes[offset..offset + size]));
+            }
+            offset += size;
+        }
+        None
+    }
+
+    fn decode_field(&self, definition: &FieldDefinition, bytes: &[u8]) -> DataField {
+        let value = DataField::read_next_field(
+            definition.size,
+            definition.base_type.val,
+            &mut Cursor::new(bytes),
+            self.endian,
+        );
+        let mut field = DataField::new(definition.definition_number, value);
+        DataField::process_read_value(
+            &mut field,
+            get_field_type_fn(self.message_type),
+            get_field_scale_fn(self.message_type),
+            get_field_offset_fn(self.message_type),
+        );
+        field
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lazy::LazyOptions;
+    use crate::protocol::data_field::DataField;
+    use crate::protocol::message_type::MessageType;
+    use crate::protocol::value::Value;
+    use crate::test_util::{heart_rate, recording};
+    use crate::Fit;
+
+    #[test]
+    fn read_lazy_test() {
+        let mut builder = recording(0..3, heart_rate);
+        builder.push(
+            MessageType::Session,
+            vec![
+                DataField::new(253, Value::Time(1_000_000_003)),
+                DataField::new(9, Value::U32(123_400)),
+            ],
+        );
+        let buf = builder.build().to_bytes().unwrap();
+        let fit = Fit::read(buf.clone()).unwrap();
+
+        let lazy = Fit::read_lazy(&buf, &LazyOptions::default()).unwrap();
+        let decoded: Vec<_> = lazy.messages.iter().map(|msg| msg.decode()).collect();
+        let eager: Vec<_> = fit.data_messages().cloned().collect();
+        assert_eq!(decoded, eager);
+
+        let options = LazyOptions {
+            skip: vec![MessageType::Record],
+        };
+        let lazy = Fit::read_lazy(&buf, &options).unwrap();
+        assert_eq!(lazy.messages.len(), 2);
+        let session = lazy.first(MessageType::Session).unwrap();
+        assert_eq!(session.get(9), Some(Value::U32(123_400)));
+        assert_eq!(session.raw(9), Some(&123_400u32.to_le_bytes()[..]));
+        assert_eq!(session.timestamp(), Some(1_000_000_003));
+        assert_eq!(session.get(2), None);
+
+        assert!(Fit::read_lazy(&buf[..buf.len() - 6], &options).is_err());
+    }
+}
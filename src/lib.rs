@@ -1,20 +1,75 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "rayon")]
+pub mod batch;
+#[cfg(feature = "std")]
+pub mod builder;
+#[cfg(feature = "std")]
+pub mod convert;
+#[cfg(feature = "std")]
+pub mod device;
+#[cfg(feature = "std")]
+pub mod diff;
+#[cfg(feature = "std")]
+pub mod edit;
+#[cfg(feature = "std")]
+mod geo;
+#[cfg(feature = "std")]
+pub mod index;
+#[cfg(feature = "std")]
+pub mod inspect;
+#[cfg(feature = "std")]
+pub mod interleave;
+pub mod lazy;
+#[cfg(feature = "std")]
+pub mod privacy;
 pub mod protocol;
+#[cfg(feature = "std")]
+mod query;
+#[cfg(feature = "std")]
+pub mod repair;
+#[cfg(feature = "async")]
+pub mod stream;
+#[cfg(feature = "std")]
+mod summary;
+#[cfg(all(test, feature = "std"))]
+mod test_util;
+#[cfg(feature = "std")]
+pub mod units;
+#[cfg(feature = "std")]
+pub mod validate;
+#[cfg(feature = "std")]
+pub mod workout;
 
+#[cfg(feature = "std")]
+use crate::builder::{field_definition, FitBuilder};
+#[cfg(feature = "std")]
+use crate::protocol::data_field::DataField;
+#[cfg(feature = "std")]
+use crate::protocol::io::invalid_data;
 use crate::protocol::io::{skip_bytes, write_bin};
-use crate::protocol::macros::get_field_value;
+#[cfg(feature = "std")]
+use crate::protocol::macros::{get_field_time, get_field_value};
 use crate::protocol::message_type::MessageType;
+#[cfg(feature = "std")]
 use crate::protocol::value::Value;
 use crate::protocol::{
     calculate_fit_crc, DataMessage, DefinitionMessage, FitDataMessage, FitDefinitionMessage,
     FitHeader, FitMessage, FitMessageHeader,
 };
-use binrw::{BinReaderExt, BinResult, BinWrite, Endian, Error};
-use std::collections::VecDeque;
-use std::fmt;
-use std::fmt::{Debug, Formatter};
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use binrw::io::{Cursor, Seek, SeekFrom, Write};
+#[cfg(feature = "std")]
+use binrw::Error;
+use binrw::{BinReaderExt, BinResult, BinWrite, Endian};
+use core::fmt;
+use core::fmt::{Debug, Formatter};
+#[cfg(feature = "std")]
 use std::fs::{read, write};
-use std::io::{Cursor, Seek, SeekFrom, Write};
-use std::ops::Div;
+#[cfg(feature = "std")]
 use std::path::Path;
 
 #[derive(Clone)]
@@ -77,14 +132,21 @@ impl Fit {
         Ok(Fit { header, data })
     }
 
+    #[cfg(feature = "std")]
     pub fn write<P: AsRef<Path>>(&self, file: P) -> BinResult<()> {
+        let buf = self.to_bytes()?;
+        write(file, &buf)?;
+        Ok(())
+    }
+
+    /// Encodes the file into memory, recomputing the data size and CRCs.
+    pub fn to_bytes(&self) -> BinResult<Vec<u8>> {
         let mut buf = Vec::with_capacity(
             (self.header.data_size + self.header.header_size as u32 + 2) as usize,
         );
         let header = self.write_buf(&mut buf)?;
         Fit::write_crc(header, &mut buf)?;
-        write(file, &buf)?;
-        Ok(())
+        Ok(buf)
     }
 
     fn write_crc(header: FitHeader, buf: &mut Vec<u8>) -> BinResult<()> {
@@ -149,6 +211,7 @@ impl Fit {
     }
 }
 
+#[cfg(feature = "std")]
 impl Fit {
     #[allow(unused)]
     pub fn merge<P: AsRef<Path>>(files: Vec<P>, path: P) -> BinResult<()> {
@@ -159,70 +222,265 @@ impl Fit {
                 "Error files is empty!",
             )));
         }
-        let file = read(files.get(0).unwrap()).unwrap();
-        let mut fit: Fit = Fit::read(file)?;
-        // find session
-        let session: Option<(usize, FitDataMessage)> = fit.get_session();
-        let mut sessions: Vec<Option<(usize, FitDataMessage)>> = vec![session];
-        for i in 1..=files.len() - 1 {
-            let f = files.get(i).unwrap();
-            let f = read(f).unwrap();
-            let mut tmp = Fit::read(f)?;
-            sessions.push(tmp.get_session());
-
-            let to_move: Vec<_> = tmp
-                .data
-                .iter()
-                .enumerate()
-                .filter(|(_, message)| match message {
-                    FitMessage::Definition(_) => false,
-                    FitMessage::Data(msg) => {
-                        return matches!(msg.data.message_type, MessageType::Record);
+        let mut fits = Vec::with_capacity(files.len());
+        for file in &files {
+            fits.push(Fit::read(read(file)?)?);
+        }
+        Fit::merge_fits(fits)?.write(path)
+    }
+
+    /// Joins activities recorded one after another into a single activity.
+    ///
+    /// Every message is kept together with its definition, except the `FileId` of the later
+    /// files. A timer stop/start pair is inserted at each file boundary unless the files
+    /// already have one. Laps are renumbered and the sessions are merged into one `Session`,
+    /// followed by a rebuilt `Activity`.
+    pub fn merge_fits(fits: Vec<Fit>) -> BinResult<Fit> {
+        let header = match fits.first() {
+            Some(fit) => fit.header.clone(),
+            None => return Err(invalid_data("no files to merge")),
+        };
+        let mut data = vec![];
+        let mut sessions = vec![];
+        let mut session_definition = None;
+        let mut activity = None;
+        let mut previous_end: Option<(u32, bool)> = None;
+        for (file_index, fit) in fits.iter().enumerate() {
+            let timer_events = fit.timer_events();
+            if let (Some((end, stopped)), Some((start, _))) = (previous_end, fit.time_range()) {
+                let mut builder = FitBuilder::new();
+                if !stopped {
+                    builder.push_timer_event(end, "stop_all");
+                }
+                if timer_events.first() != Some(&"start") {
+                    builder.push_timer_event(start.max(end), "start");
+                }
+                data.extend(builder.build().data);
+            }
+
+            for (index, message) in fit.data.iter().enumerate() {
+                if let FitMessage::Data(msg) = message {
+                    match msg.data.message_type {
+                        MessageType::Session => {
+                            if session_definition.is_none() {
+                                session_definition = fit.definition_of(index);
+                            }
+                            sessions.push(msg.clone());
+                            continue;
+                        }
+                        MessageType::Activity => {
+                            if activity.is_none() {
+                                activity = fit.definition_of(index).zip(Some(msg.clone()));
+                            }
+                            continue;
+                        }
+                        MessageType::FileId | MessageType::FileCreator if file_index > 0 => {
+                            continue
+                        }
+                        _ => {}
                     }
-                })
-                .map(|(i, _)| i)
-                .collect();
-
-            for i in to_move.into_iter().rev() {
-                // Here, we directly take the message without wrapping it in an Option.
-                let message = tmp.data.swap_remove(i);
-                fit.data.push(message);
+                }
+                data.push(message.clone());
+            }
+
+            if let Some((_, end)) = fit.time_range() {
+                let stopped = timer_events.last().is_some_and(|t| is_timer_stop(t));
+                previous_end = Some((end, stopped));
+            }
+        }
+
+        let mut num_laps = 0_u16;
+        for message in &mut data {
+            if let FitMessage::Data(msg) = message {
+                if msg.data.message_type == MessageType::Lap {
+                    put_field!(msg.data.values, 254, Value::U16(num_laps));
+                    num_laps = num_laps.saturating_add(1);
+                }
             }
         }
 
-        fit.replace_session(sessions);
-        fit.write(path)
+        let end_time = match previous_end {
+            Some((end, _)) => end,
+            None => return Err(invalid_data("files to merge have no timestamps")),
+        };
+        let mut total_timer_time = None;
+        let session = Fit::merge_sessions(sessions);
+        if let (Some(definition), Some(mut session)) = (session_definition, session) {
+            put_field!(session.data.values, 254, Value::U16(0));
+            put_field!(session.data.values, 25, Value::U16(0));
+            put_field!(session.data.values, 26, Value::U16(num_laps));
+            total_timer_time = get_field_value(8, &session.data.values);
+            data.push(FitMessage::Definition(definition));
+            data.push(FitMessage::Data(session));
+        }
+
+        match activity {
+            Some((definition, mut activity)) => {
+                let values = &mut activity.data.values;
+                if let (Some(Value::Time(timestamp)), Some(Value::Time(local))) =
+                    (get_field_value(253, values), get_field_value(5, values))
+                {
+                    let local = local as i64 + end_time as i64 - timestamp as i64;
+                    update_field!(values, 5, Value::Time(local as u32));
+                }
+                update_field!(values, 253, Value::Time(end_time));
+                update_field!(values, 1, Value::U16(1));
+                if let Some(total_timer_time) = total_timer_time {
+                    update_field!(values, 0, total_timer_time);
+                }
+                data.push(FitMessage::Definition(definition));
+                data.push(FitMessage::Data(activity));
+            }
+            None => {
+                let mut builder = FitBuilder::new();
+                let mut values = vec![
+                    DataField::new(253, Value::Time(end_time)),
+                    DataField::new(1, Value::U16(1)),
+                    DataField::new(2, Value::Enum("manual")),
+                    DataField::new(3, Value::Enum("activity")),
+                    DataField::new(4, Value::Enum("stop")),
+                ];
+                if let Some(total_timer_time) = total_timer_time {
+                    values.push(DataField::new(0, total_timer_time));
+                }
+                builder.push(MessageType::Activity, values);
+                data.extend(builder.build().data);
+            }
+        }
+        let mut fit = Fit { header, data };
+        fit.define_added_fields();
+        Ok(fit)
     }
 
-    fn replace_session(&mut self, sessions: Vec<Option<(usize, FitDataMessage)>>) {
-        let mut index = 0;
-        let mut session_vec = vec![];
-        for session in sessions {
-            match session {
-                None => {}
-                Some((i, s)) => {
-                    if index == 0 {
-                        index = i;
+    /// Pairs every data message with the definition it was encoded with.
+    pub(crate) fn defined_messages(&self) -> Vec<(&FitDefinitionMessage, &FitDataMessage)> {
+        let mut definitions: Vec<&FitDefinitionMessage> = vec![];
+        let mut messages = vec![];
+        for message in &self.data {
+            match message {
+                FitMessage::Definition(def) => {
+                    definitions.retain(|d| d.header.local_num != def.header.local_num);
+                    definitions.push(def);
+                }
+                FitMessage::Data(msg) => {
+                    let local_num = msg.header.local_num;
+                    if let Some(def) = definitions.iter().find(|d| d.header.local_num == local_num)
+                    {
+                        messages.push((*def, msg));
                     }
-                    session_vec.push(s);
                 }
             }
         }
+        messages
+    }
 
-        let session = Fit::merge_sessions(session_vec);
-        match session {
-            None => {}
-            Some(session) => {
-                self.data[index] = FitMessage::Data(session);
+    /// Gives the data messages that gained fields through `put_field!` a definition that
+    /// covers them, re-encoding the file with `FitBuilder` if any message needs one.
+    pub(crate) fn define_added_fields(&mut self) {
+        let messages = self.defined_messages();
+        if messages
+            .iter()
+            .all(|(def, msg)| def.data.fields.len() == msg.data.values.len())
+        {
+            return;
+        }
+        let mut builder = FitBuilder::new();
+        for (def, msg) in messages {
+            let message_type = msg.data.message_type;
+            let mut fields = def.data.fields.clone();
+            let mut values = msg.data.values.clone();
+            let added = values.split_off(fields.len().min(values.len()));
+            fields.truncate(values.len());
+            for mut field in added {
+                if let Some(definition) = field_definition(message_type, &mut field) {
+                    fields.push(definition);
+                    values.push(field);
+                }
+            }
+            builder.push_defined(message_type, fields, values);
+        }
+        self.data = builder.build().data;
+    }
+
+    /// Inserts messages at `index`. Local message numbers they redefine get their previous
+    /// definition back afterwards, so the messages that follow still decode.
+    pub(crate) fn insert_messages(&mut self, index: usize, messages: Vec<FitMessage>) {
+        let mut restore: Vec<FitMessage> = vec![];
+        for message in &messages {
+            if let FitMessage::Definition(def) = message {
+                let local_num = def.header.local_num;
+                let redefined = restore.iter().any(
+                    |m| matches!(m, FitMessage::Definition(d) if d.header.local_num == local_num),
+                );
+                if redefined {
+                    continue;
+                }
+                let previous = self.data[..index].iter().rev().find_map(|m| match m {
+                    FitMessage::Definition(d) if d.header.local_num == local_num => Some(d),
+                    _ => None,
+                });
+                restore.extend(previous.cloned().map(FitMessage::Definition));
             }
         }
+        self.data
+            .splice(index..index, messages.into_iter().chain(restore));
+    }
+
+    /// Returns the definition a data message at `index` was encoded with.
+    fn definition_of(&self, index: usize) -> Option<FitDefinitionMessage> {
+        let local_num = match &self.data[index] {
+            FitMessage::Data(msg) => msg.header.local_num,
+            FitMessage::Definition(_) => return None,
+        };
+        self.data[..index]
+            .iter()
+            .rev()
+            .find_map(|message| match message {
+                FitMessage::Definition(def) if def.header.local_num == local_num => {
+                    Some(def.clone())
+                }
+                _ => None,
+            })
+    }
+
+    /// The earliest and latest message timestamps.
+    fn time_range(&self) -> Option<(u32, u32)> {
+        self.data
+            .iter()
+            .filter_map(|message| match message {
+                FitMessage::Data(msg) => get_field_time(253, &msg.data.values),
+                FitMessage::Definition(_) => None,
+            })
+            .fold(None, |range, t| match range {
+                None => Some((t, t)),
+                Some((start, end)) => Some((start.min(t), end.max(t))),
+            })
+    }
+
+    /// The `event_type` of each `timer` event, in file order.
+    pub(crate) fn timer_events(&self) -> Vec<&'static str> {
+        self.data
+            .iter()
+            .filter_map(|message| match message {
+                FitMessage::Data(msg) if msg.data.message_type == MessageType::Event => {
+                    let values = &msg.data.values;
+                    match (get_field_value(0, values), get_field_value(1, values)) {
+                        (Some(Value::Enum("timer")), Some(Value::Enum(event_type))) => {
+                            Some(event_type)
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            })
+            .collect()
     }
 
-    fn merge_sessions(mut sessions: Vec<FitDataMessage>) -> Option<FitDataMessage> {
+    fn merge_sessions(sessions: Vec<FitDataMessage>) -> Option<FitDataMessage> {
         if sessions.is_empty() {
             return None;
         }
-        let mut merged_session = sessions.remove(0);
+        let mut merged_session = sessions[0].clone();
         // max
         let mut max_stop_timestamp = Value::Time(u32::MIN);
         let mut max_speed = Value::U16(u16::MIN);
@@ -246,32 +504,32 @@ impl Fit {
         let mut total_ascent = Value::U16(0_u16);
         let mut total_descent = Value::U16(0_u16);
         // avg
-        let mut avg_speed = Value::I32(0_i32);
-        let mut avg_speed_count = 0_i32;
-        let mut avg_power = Value::I32(0_i32);
-        let mut avg_power_count = 0_i32;
-        let mut normal_power = Value::I32(0_i32);
-        let mut normal_power_count = 0_i32;
-        let mut avg_altitude = Value::I32(0_i32);
-        let mut avg_altitude_count = 0_i32;
-        let mut avg_grade = Value::I32(0_i32);
-        let mut avg_grade_count = 0_i32;
-        let mut avg_pos_grade = Value::I32(0_i32);
-        let mut avg_pos_grade_count = 0_i32;
-        let mut avg_neg_grade = Value::I32(0_i32);
-        let mut avg_neg_grade_count = 0_i32;
-        let mut avg_pos_vertical_speed = Value::I32(0_i32);
-        let mut avg_pos_vertical_speed_count = 0_i32;
-        let mut avg_neg_vertical_speed = Value::I32(0_i32);
-        let mut avg_neg_vertical_speed_count = 0_i32;
-        let mut avg_heart_rate = Value::I32(0_i32);
-        let mut avg_heart_rate_count = 0_i32;
-        let mut avg_cadence = Value::I32(0_i32);
-        let mut avg_cadence_count = 0_i32;
-        let mut avg_temperature = Value::I32(0_i32);
-        let mut avg_temperature_count = 0_i32;
-
-        for session in sessions {
+        let mut avg_speed = 0_i64;
+        let mut avg_speed_count = 0_i64;
+        let mut avg_power = 0_i64;
+        let mut avg_power_count = 0_i64;
+        let mut normal_power = 0_i64;
+        let mut normal_power_count = 0_i64;
+        let mut avg_altitude = 0_i64;
+        let mut avg_altitude_count = 0_i64;
+        let mut avg_grade = 0_i64;
+        let mut avg_grade_count = 0_i64;
+        let mut avg_pos_grade = 0_i64;
+        let mut avg_pos_grade_count = 0_i64;
+        let mut avg_neg_grade = 0_i64;
+        let mut avg_neg_grade_count = 0_i64;
+        let mut avg_pos_vertical_speed = 0_i64;
+        let mut avg_pos_vertical_speed_count = 0_i64;
+        let mut avg_neg_vertical_speed = 0_i64;
+        let mut avg_neg_vertical_speed_count = 0_i64;
+        let mut avg_heart_rate = 0_i64;
+        let mut avg_heart_rate_count = 0_i64;
+        let mut avg_cadence = 0_i64;
+        let mut avg_cadence_count = 0_i64;
+        let mut avg_temperature = 0_i64;
+        let mut avg_temperature_count = 0_i64;
+
+        for session in &sessions {
             merge_stats!(
                 // max
                 max 253, max_stop_timestamp, session,
@@ -336,15 +594,15 @@ impl Fit {
         update_field!(merged_session.data.values, 23, total_descent);
         // avg
         if avg_speed_count > 0 {
-            let avg_speed = <Value as Into<i32>>::into(avg_speed).div(avg_speed_count);
+            let avg_speed = avg_speed / avg_speed_count;
             update_field!(merged_session.data.values, 14, Value::U16(avg_speed as u16));
         }
         if avg_power_count > 0 {
-            let avg_power = <Value as Into<i32>>::into(avg_power).div(avg_power_count);
+            let avg_power = avg_power / avg_power_count;
             update_field!(merged_session.data.values, 20, Value::U16(avg_power as u16));
         }
         if normal_power_count > 0 {
-            let normal_power = <Value as Into<i32>>::into(normal_power).div(normal_power_count);
+            let normal_power = normal_power / normal_power_count;
             update_field!(
                 merged_session.data.values,
                 34,
@@ -352,7 +610,7 @@ impl Fit {
             );
         }
         if avg_altitude_count > 0 {
-            let avg_altitude = <Value as Into<i32>>::into(avg_altitude).div(avg_altitude_count);
+            let avg_altitude = avg_altitude / avg_altitude_count;
             update_field!(
                 merged_session.data.values,
                 49,
@@ -360,11 +618,11 @@ impl Fit {
             );
         }
         if avg_grade_count > 0 {
-            let avg_grade = <Value as Into<i32>>::into(avg_grade).div(avg_grade_count);
+            let avg_grade = avg_grade / avg_grade_count;
             update_field!(merged_session.data.values, 52, Value::I16(avg_grade as i16));
         }
         if avg_pos_grade_count > 0 {
-            let avg_pos_grade = <Value as Into<i32>>::into(avg_pos_grade).div(avg_pos_grade_count);
+            let avg_pos_grade = avg_pos_grade / avg_pos_grade_count;
             update_field!(
                 merged_session.data.values,
                 53,
@@ -372,7 +630,7 @@ impl Fit {
             );
         }
         if avg_neg_grade_count > 0 {
-            let avg_neg_grade = <Value as Into<i32>>::into(avg_neg_grade).div(avg_neg_grade_count);
+            let avg_neg_grade = avg_neg_grade / avg_neg_grade_count;
             update_field!(
                 merged_session.data.values,
                 54,
@@ -380,8 +638,7 @@ impl Fit {
             );
         }
         if avg_pos_vertical_speed_count > 0 {
-            let avg_pos_vertical_speed = <Value as Into<i32>>::into(avg_pos_vertical_speed)
-                .div(avg_pos_vertical_speed_count);
+            let avg_pos_vertical_speed = avg_pos_vertical_speed / avg_pos_vertical_speed_count;
             update_field!(
                 merged_session.data.values,
                 60,
@@ -389,8 +646,7 @@ impl Fit {
             );
         }
         if avg_neg_vertical_speed_count > 0 {
-            let avg_neg_vertical_speed = <Value as Into<i32>>::into(avg_neg_vertical_speed)
-                .div(avg_neg_vertical_speed_count);
+            let avg_neg_vertical_speed = avg_neg_vertical_speed / avg_neg_vertical_speed_count;
             update_field!(
                 merged_session.data.values,
                 61,
@@ -398,8 +654,7 @@ impl Fit {
             );
         }
         if avg_heart_rate_count > 0 {
-            let avg_heart_rate =
-                <Value as Into<i32>>::into(avg_heart_rate).div(avg_heart_rate_count);
+            let avg_heart_rate = avg_heart_rate / avg_heart_rate_count;
             update_field!(
                 merged_session.data.values,
                 16,
@@ -407,12 +662,11 @@ impl Fit {
             );
         }
         if avg_cadence_count > 0 {
-            let avg_cadence = <Value as Into<i32>>::into(avg_cadence).div(avg_cadence_count);
+            let avg_cadence = avg_cadence / avg_cadence_count;
             update_field!(merged_session.data.values, 18, Value::U8(avg_cadence as u8));
         }
         if avg_temperature_count > 0 {
-            let avg_temperature =
-                <Value as Into<i32>>::into(avg_temperature).div(avg_temperature_count);
+            let avg_temperature = avg_temperature / avg_temperature_count;
             update_field!(
                 merged_session.data.values,
                 57,
@@ -423,18 +677,102 @@ impl Fit {
         Some(merged_session)
     }
 
+    /// The first `Session` and its position in `data`.
     pub fn get_session(&self) -> Option<(usize, FitDataMessage)> {
-        for (index, message) in self.data.iter().enumerate() {
-            match message {
-                FitMessage::Definition(_) => {}
-                FitMessage::Data(msg) => match msg.data.message_type {
-                    MessageType::Session => {
-                        return Some((index, msg.clone()));
-                    }
-                    _ => {}
-                },
-            };
+        self.data
+            .iter()
+            .enumerate()
+            .find_map(|(index, message)| match message {
+                FitMessage::Data(msg) if msg.data.message_type == MessageType::Session => {
+                    Some((index, msg.clone()))
+                }
+                _ => None,
+            })
+    }
+}
+
+/// Whether a timer `event_type` stops the timer.
+#[cfg(feature = "std")]
+pub(crate) fn is_timer_stop(event_type: &str) -> bool {
+    matches!(
+        event_type,
+        "stop" | "stop_all" | "stop_disable" | "stop_disable_all"
+    )
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::protocol::data_field::DataField;
+    use crate::protocol::message_type::MessageType;
+    use crate::protocol::value::Value;
+    use crate::test_util::{activity_builder, push_records, START};
+    use crate::Fit;
+
+    fn activity(start: u32, timer_events: bool, lap_index: bool) -> Fit {
+        let mut builder = activity_builder();
+        if timer_events {
+            builder.push_timer_event(START + start, "start");
+        }
+        push_records(&mut builder, [start, start + 60], |_| {
+            vec![DataField::new(3, Value::U8(120))]
+        });
+        let mut lap = vec![
+            DataField::new(253, Value::Time(START + start + 60)),
+            DataField::new(2, Value::Time(START + start)),
+        ];
+        if lap_index {
+            lap.push(DataField::new(254, Value::U16(0)));
+        }
+        builder.push(MessageType::Lap, lap);
+        if timer_events {
+            builder.push_timer_event(START + start + 60, "stop_all");
         }
-        None
+        builder.push(
+            MessageType::Session,
+            vec![
+                DataField::new(253, Value::Time(START + start + 60)),
+                DataField::new(2, Value::Time(START + start)),
+                DataField::new(7, Value::U32(60_000)),
+                DataField::new(8, Value::U32(60_000)),
+                DataField::new(26, Value::U16(1)),
+            ],
+        );
+        builder.push(
+            MessageType::Activity,
+            vec![
+                DataField::new(253, Value::Time(START + start + 60)),
+                DataField::new(0, Value::U32(60_000)),
+                DataField::new(1, Value::U16(1)),
+            ],
+        );
+        builder.build()
+    }
+
+    #[test]
+    fn merge_fits_test() {
+        let first = activity(0, true, true);
+        let second = activity(600, false, false);
+        let fit = Fit::merge_fits(vec![first, second]).unwrap();
+        let fit = Fit::read(fit.to_bytes().unwrap()).unwrap();
+        assert_eq!(fit.messages(MessageType::FileId).count(), 1);
+        assert_eq!(fit.messages(MessageType::Record).count(), 4);
+        assert_eq!(fit.messages(MessageType::Event).count(), 3);
+        let laps: Vec<_> = fit
+            .messages(MessageType::Lap)
+            .map(|lap| lap.get(254).cloned())
+            .collect();
+        assert_eq!(laps, vec![Some(Value::U16(0)), Some(Value::U16(1))]);
+
+        assert_eq!(fit.messages(MessageType::Session).count(), 1);
+        let session = fit.first(MessageType::Session).unwrap();
+        assert_eq!(session.get(8), Some(&Value::U32(120_000)));
+        assert_eq!(session.get(26), Some(&Value::U16(2)));
+        assert_eq!(fit.messages(MessageType::Activity).count(), 1);
+        let activity = fit.first(MessageType::Activity).unwrap();
+        assert_eq!(activity.get(0), Some(&Value::U32(120_000)));
+        assert_eq!(activity.get(253), Some(&Value::Time(START + 660)));
+
+        let untimed = activity_builder().build();
+        assert!(Fit::merge_fits(vec![untimed.clone(), untimed]).is_err());
     }
 }
@@ -0,0 +1,227 @@
+use crate::geo::haversine;
+use crate::protocol::data_field::DataField;
+use crate::protocol::macros::{get_field_scaled, get_position};
+use crate::protocol::message_type::MessageType;
+use crate::protocol::value::Value;
+use crate::protocol::FitMessage;
+use crate::{update_field, Fit};
+
+// Unset coordinates are encoded as 0x7FFFFFFF semicircles, which decodes to 180°.
+const BLANK: Value = Value::F32(180.0);
+
+/// A circle whose contents are hidden, with the radius in metres.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PrivacyZone {
+    pub lat: f64,
+    pub long: f64,
+    pub radius: f64,
+}
+
+/// Settings for `Fit::anonymize`.
+#[derive(Debug, Clone, Default)]
+pub struct PrivacyOptions {
+    pub zones: Vec<PrivacyZone>,
+    /// Metres hidden at the start of the track.
+    pub hide_start: f64,
+    /// Metres hidden at the end of the track.
+    pub hide_end: f64,
+    /// Drop records and course points in hidden areas instead of blanking their coordinates.
+    pub remove: bool,
+}
+
+impl PrivacyOptions {
+    fn in_zone(&self, position: (f64, f64)) -> bool {
+        self.zones
+            .iter()
+            .any(|zone| haversine((zone.lat, zone.long), position) <= zone.radius)
+    }
+}
+
+impl Fit {
+    /// Hides positions inside the privacy zones and along the first and last metres of the
+    /// track, and strips the serial numbers from `FileId` and `DeviceInfo`.
+    ///
+    /// Hidden `Record` and `CoursePoint` positions are blanked (or the messages dropped with
+    /// `options.remove`), as are `Lap` and `Session` start/end positions that fall in a
+    /// hidden area. The session bounding box is rebuilt from the visible records.
+    pub fn anonymize(&mut self, options: &PrivacyOptions) {
+        let track: Vec<((f64, f64), Option<f64>)> = self
+            .data
+            .iter()
+            .filter_map(|message| match message {
+                FitMessage::Data(msg) if msg.data.message_type == MessageType::Record => {
+                    let values = &msg.data.values;
+                    Some((
+                        get_position(0, 1, values)?,
+                        get_field_scaled(MessageType::Record, 5, values),
+                    ))
+                }
+                _ => None,
+            })
+            .collect();
+        // Distance along the track, from the records or else from the positions.
+        let mut along = Vec::with_capacity(track.len());
+        for (i, (position, distance)) in track.iter().enumerate() {
+            let d = match (distance, i) {
+                (Some(distance), _) => *distance,
+                (None, 0) => 0.0,
+                (None, _) => along[i - 1] + haversine(track[i - 1].0, *position),
+            };
+            along.push(d);
+        }
+        let (first, last) = match (along.first(), along.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => (0.0, 0.0),
+        };
+        let hidden_track: Vec<(f64, f64)> = track
+            .iter()
+            .zip(&along)
+            .filter(|(_, d)| **d - first < options.hide_start || last - **d < options.hide_end)
+            .map(|((position, _), _)| *position)
+            .collect();
+        let hidden = |position: (f64, f64)| {
+            options.in_zone(position)
+                || hidden_track
+                    .iter()
+                    .any(|point| haversine(*point, position) < 1.0)
+        };
+
+        let (mut north, mut east, mut south, mut west): (Option<f64>, _, Option<f64>, _) =
+            (None, None, None, None);
+        self.data.retain_mut(|message| {
+            let msg = match message {
+                FitMessage::Data(msg) => msg,
+                FitMessage::Definition(_) => return true,
+            };
+            let values = &mut msg.data.values;
+            let positions: &[(u8, u8)] = match msg.data.message_type {
+                MessageType::Record => &[(0, 1)],
+                MessageType::CoursePoint => &[(2, 3)],
+                MessageType::Lap => &[(3, 4), (5, 6)],
+                MessageType::Session => &[(3, 4)],
+                MessageType::FileId | MessageType::DeviceInfo => {
+                    strip_serial(values);
+                    return true;
+                }
+                _ => return true,
+            };
+            for (lat, long) in positions {
+                let position = match get_position(*lat, *long, values) {
+                    Some(position) => position,
+                    None => continue,
+                };
+                if !hidden(position) {
+                    if msg.data.message_type == MessageType::Record {
+                        north = Some(north.map_or(position.0, |n: f64| n.max(position.0)));
+                        south = Some(south.map_or(position.0, |s: f64| s.min(position.0)));
+                        east = Some(east.map_or(position.1, |e: f64| e.max(position.1)));
+                        west = Some(west.map_or(position.1, |w: f64| w.min(position.1)));
+                    }
+                    continue;
+                }
+                if options.remove
+                    && matches!(
+                        msg.data.message_type,
+                        MessageType::Record | MessageType::CoursePoint
+                    )
+                {
+                    return false;
+                }
+                update_field!(values, *lat, BLANK);
+                update_field!(values, *long, BLANK);
+            }
+            true
+        });
+
+        let coordinate = |v: Option<f64>| v.map_or(BLANK, |v| Value::F32(v as f32));
+        for message in &mut self.data {
+            if let FitMessage::Data(msg) = message {
+                if msg.data.message_type == MessageType::Session {
+                    let values = &mut msg.data.values;
+                    update_field!(values, 29, coordinate(north));
+                    update_field!(values, 30, coordinate(east));
+                    update_field!(values, 31, coordinate(south));
+                    update_field!(values, 32, coordinate(west));
+                }
+            }
+        }
+    }
+}
+
+/// Resets `serial_number` to its invalid value, keeping the field width.
+fn strip_serial(values: &mut [DataField]) {
+    if let Some(field) = values.iter_mut().find(|field| field.field_num == 3) {
+        field.value = match field.value {
+            // serial_number is a uint32z, for which zero marks an unset value.
+            Value::U32(_) => Value::U32(0),
+            Value::U16(_) => Value::U16(0),
+            Value::U8(_) => Value::U8(0),
+            _ => Value::None,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::FitBuilder;
+    use crate::privacy::{PrivacyOptions, PrivacyZone};
+    use crate::protocol::data_field::DataField;
+    use crate::protocol::macros::{get_field_value, get_position};
+    use crate::protocol::message_type::MessageType;
+    use crate::protocol::value::Value;
+    use crate::protocol::FitMessage;
+    use crate::Fit;
+
+    #[test]
+    fn anonymize_test() {
+        let mut builder = FitBuilder::new();
+        builder.push(
+            MessageType::FileId,
+            vec![
+                DataField::new(0, Value::Enum("activity")),
+                DataField::new(3, Value::U32(3_912_345_678)),
+            ],
+        );
+        // Roughly 111 m between points heading north.
+        for i in 0..10 {
+            builder.push(
+                MessageType::Record,
+                vec![
+                    DataField::new(253, Value::Time(1_000_000_000 + i)),
+                    DataField::new(0, Value::F32(45.0 + i as f32 * 0.001)),
+                    DataField::new(1, Value::F32(7.0)),
+                ],
+            );
+        }
+        let mut fit = builder.build();
+        fit.anonymize(&PrivacyOptions {
+            zones: vec![PrivacyZone {
+                lat: 45.005,
+                long: 7.0,
+                radius: 50.0,
+            }],
+            hide_start: 200.0,
+            ..PrivacyOptions::default()
+        });
+        let fit = Fit::read(fit.to_bytes().unwrap()).unwrap();
+
+        let mut visible = vec![];
+        for message in &fit.data {
+            if let FitMessage::Data(msg) = message {
+                match msg.data.message_type {
+                    MessageType::FileId => {
+                        assert_eq!(get_field_value(3, &msg.data.values), Some(Value::U32(0)))
+                    }
+                    MessageType::Record => {
+                        visible.push(get_position(0, 1, &msg.data.values).is_some())
+                    }
+                    _ => {}
+                }
+            }
+        }
+        assert_eq!(
+            visible,
+            vec![false, false, true, true, true, false, true, true, true, true]
+        );
+    }
+}
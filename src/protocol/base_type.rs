@@ -0,0 +1,94 @@
+use crate::protocol::get_field_string_value::FieldType;
+
+pub const ENUM: u8 = 0x00;
+pub const SINT8: u8 = 0x01;
+pub const UINT8: u8 = 0x02;
+pub const SINT16: u8 = 0x83;
+pub const UINT16: u8 = 0x84;
+pub const SINT32: u8 = 0x85;
+pub const UINT32: u8 = 0x86;
+pub const STRING: u8 = 0x07;
+pub const FLOAT32: u8 = 0x88;
+pub const FLOAT64: u8 = 0x89;
+pub const UINT8Z: u8 = 0x0A;
+pub const UINT16Z: u8 = 0x8B;
+pub const UINT32Z: u8 = 0x8C;
+pub const BYTE: u8 = 0x0D;
+pub const SINT64: u8 = 0x8E;
+pub const UINT64: u8 = 0x8F;
+pub const UINT64Z: u8 = 0x90;
+
+/// Size in bytes of a single value of the given base type; strings and bytes count per character.
+pub fn base_type_size(base_type: u8) -> u8 {
+    match base_type & 0x1F {
+        3 | 4 | 11 => 2,
+        5 | 6 | 8 | 12 => 4,
+        9 | 14..=16 => 8,
+        _ => 1,
+    }
+}
+
+/// The profile name of a base type, e.g. `"uint16"`.
+pub fn base_type_name(base_type: u8) -> &'static str {
+    match base_type & 0x1F {
+        0 => "enum",
+        1 => "sint8",
+        2 => "uint8",
+        3 => "sint16",
+        4 => "uint16",
+        5 => "sint32",
+        6 => "uint32",
+        7 => "string",
+        8 => "float32",
+        9 => "float64",
+        10 => "uint8z",
+        11 => "uint16z",
+        12 => "uint32z",
+        13 => "byte",
+        14 => "sint64",
+        15 => "uint64",
+        16 => "uint64z",
+        _ => "unknown",
+    }
+}
+
+/// The base type the SDK profile declares for a `FieldType`, or `None` for variable length
+/// strings and fields unknown to the profile.
+pub fn profile_base_type(field_type: FieldType) -> Option<u8> {
+    match field_type {
+        FieldType::None | FieldType::String => None,
+        FieldType::Uint8 => Some(UINT8),
+        FieldType::Uint8Z | FieldType::SportBits0 | FieldType::FileFlags => Some(UINT8Z),
+        FieldType::Sint8 => Some(SINT8),
+        FieldType::Uint16
+        | FieldType::Manufacturer
+        | FieldType::MessageIndex
+        | FieldType::MesgNum
+        | FieldType::LeftRightBalance100
+        | FieldType::AttitudeValidity
+        | FieldType::ExerciseCategory
+        | FieldType::FitBaseUnit
+        | FieldType::UserLocalId
+        | FieldType::Weight => Some(UINT16),
+        FieldType::Uint16Z => Some(UINT16Z),
+        FieldType::Sint16 => Some(SINT16),
+        FieldType::Uint32
+        | FieldType::Timestamp
+        | FieldType::DateTime
+        | FieldType::LocalDateTime
+        | FieldType::LocaltimeIntoDay
+        | FieldType::AutoActivityDetect => Some(UINT32),
+        FieldType::Uint32Z
+        | FieldType::WorkoutCapabilities
+        | FieldType::ConnectivityCapabilities
+        | FieldType::CourseCapabilities => Some(UINT32Z),
+        FieldType::Sint32 | FieldType::Coordinates => Some(SINT32),
+        FieldType::Float32 => Some(FLOAT32),
+        FieldType::Byte => Some(BYTE),
+        FieldType::DeviceIndex
+        | FieldType::BacklightTimeout
+        | FieldType::FitBaseType
+        | FieldType::LeftRightBalance => Some(UINT8),
+        _ => Some(ENUM),
+    }
+}
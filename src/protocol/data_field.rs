@@ -14,10 +14,12 @@ use crate::protocol::value::Value;
 use crate::protocol::{
     DefinitionMessage, FieldDefinition, MatchFieldTypeFn, MatchOffsetFn, MatchScaleFn,
 };
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use binrw::io::{Read, Seek, Write};
 use binrw::{BinResult, Endian};
-use copyless::VecHelper;
-use std::fmt::{Debug, Formatter};
-use std::io::{Read, Seek, Write};
+use core::fmt::{Debug, Formatter};
 
 #[derive(Clone, PartialEq)]
 pub struct DataField {
@@ -27,7 +29,7 @@ pub struct DataField {
 }
 
 impl Debug for DataField {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         write!(f, " {{ t: {:?}, v: {:?} }}", &self.field_num, &self.value)
     }
 }
@@ -56,9 +58,7 @@ impl DataField {
         } else {
             for fd in fields.iter() {
                 let data = DataField::read_next_field(fd.size, fd.base_type.val, reader, endian);
-                values
-                    .alloc()
-                    .init(DataField::new(fd.definition_number, data));
+                values.push(DataField::new(fd.definition_number, data));
             }
             // check each value in case the raw value needs further processing
             let scales = get_field_scale_fn(message_type);
@@ -319,7 +319,7 @@ impl DataField {
     }
 
     #[allow(unused_must_use)]
-    fn process_read_value(
+    pub(crate) fn process_read_value(
         v: &mut DataField,
         fields: MatchFieldTypeFn,
         scales: MatchScaleFn,
@@ -330,19 +330,19 @@ impl DataField {
             FieldType::Coordinates => {
                 if let Value::I32(ref inner) = v.value {
                     let coord = *inner as f32 * COORD_SEMICIRCLES_CALC;
-                    std::mem::replace(&mut v.value, Value::F32(coord));
+                    core::mem::replace(&mut v.value, Value::F32(coord));
                 }
             }
             FieldType::Timestamp | FieldType::DateTime => {
                 if let Value::U32(ref inner) = v.value {
                     let date = *inner + PSEUDO_EPOCH;
-                    std::mem::replace(&mut v.value, Value::Time(date));
+                    core::mem::replace(&mut v.value, Value::Time(date));
                 }
             }
             FieldType::LocalDateTime => {
                 if let Value::U32(ref inner) = v.value {
                     let time = *inner + PSEUDO_EPOCH - 3600;
-                    std::mem::replace(&mut v.value, Value::Time(time));
+                    core::mem::replace(&mut v.value, Value::Time(time));
                 }
             }
             FieldType::String | FieldType::LocaltimeIntoDay => {}
@@ -363,11 +363,11 @@ impl DataField {
             f => {
                 if let Value::U8(k) = v.value {
                     if let Some(t) = get_field_string_value(f, usize::from(k)) {
-                        std::mem::replace(&mut v.value, Value::Enum(t));
+                        core::mem::replace(&mut v.value, Value::Enum(t));
                     }
                 } else if let Value::U16(k) = v.value {
                     if let Some(t) = get_field_string_value(f, usize::from(k)) {
-                        std::mem::replace(&mut v.value, Value::Enum(t));
+                        core::mem::replace(&mut v.value, Value::Enum(t));
                     }
                 }
             }
@@ -0,0 +1,200 @@
+use crate::protocol::message_type::MessageType;
+
+/// Profile names of the fields of the messages activity files are made of. Fields of
+/// other messages, and less common fields, have no name here.
+pub fn get_field_name(message_type: MessageType, field_num: u8) -> Option<&'static str> {
+    let common = match field_num {
+        253 => Some("timestamp"),
+        254 => Some("message_index"),
+        _ => None,
+    };
+    let name = match message_type {
+        MessageType::FileId => match field_num {
+            0 => "type",
+            1 => "manufacturer",
+            2 => "product",
+            3 => "serial_number",
+            4 => "time_created",
+            5 => "number",
+            8 => "product_name",
+            _ => return common,
+        },
+        MessageType::FileCreator => match field_num {
+            0 => "software_version",
+            1 => "hardware_version",
+            _ => return common,
+        },
+        MessageType::DeviceInfo => match field_num {
+            0 => "device_index",
+            1 => "device_type",
+            2 => "manufacturer",
+            3 => "serial_number",
+            4 => "product",
+            5 => "software_version",
+            6 => "hardware_version",
+            7 => "cum_operating_time",
+            10 => "battery_voltage",
+            11 => "battery_status",
+            18 => "sensor_position",
+            19 => "descriptor",
+            20 => "ant_transmission_type",
+            21 => "ant_device_number",
+            22 => "ant_network",
+            25 => "source_type",
+            27 => "product_name",
+            _ => return common,
+        },
+        MessageType::Event => match field_num {
+            0 => "event",
+            1 => "event_type",
+            2 => "data16",
+            3 => "data",
+            4 => "event_group",
+            _ => return common,
+        },
+        MessageType::Record => match field_num {
+            0 => "position_lat",
+            1 => "position_long",
+            2 => "altitude",
+            3 => "heart_rate",
+            4 => "cadence",
+            5 => "distance",
+            6 => "speed",
+            7 => "power",
+            9 => "grade",
+            13 => "temperature",
+            29 => "accumulated_power",
+            30 => "left_right_balance",
+            33 => "calories",
+            39 => "vertical_oscillation",
+            40 => "stance_time_percent",
+            41 => "stance_time",
+            53 => "fractional_cadence",
+            73 => "enhanced_speed",
+            78 => "enhanced_altitude",
+            _ => return common,
+        },
+        MessageType::Lap => match field_num {
+            0 => "event",
+            1 => "event_type",
+            2 => "start_time",
+            3 => "start_position_lat",
+            4 => "start_position_long",
+            5 => "end_position_lat",
+            6 => "end_position_long",
+            7 => "total_elapsed_time",
+            8 => "total_timer_time",
+            9 => "total_distance",
+            10 => "total_cycles",
+            11 => "total_calories",
+            13 => "avg_speed",
+            14 => "max_speed",
+            15 => "avg_heart_rate",
+            16 => "max_heart_rate",
+            17 => "avg_cadence",
+            18 => "max_cadence",
+            19 => "avg_power",
+            20 => "max_power",
+            21 => "total_ascent",
+            22 => "total_descent",
+            23 => "intensity",
+            24 => "lap_trigger",
+            25 => "sport",
+            26 => "event_group",
+            32 => "num_lengths",
+            33 => "normalized_power",
+            39 => "sub_sport",
+            52 => "total_moving_time",
+            63 => "min_heart_rate",
+            110 => "enhanced_avg_speed",
+            111 => "enhanced_max_speed",
+            _ => return common,
+        },
+        MessageType::Session => match field_num {
+            0 => "event",
+            1 => "event_type",
+            2 => "start_time",
+            3 => "start_position_lat",
+            4 => "start_position_long",
+            5 => "sport",
+            6 => "sub_sport",
+            7 => "total_elapsed_time",
+            8 => "total_timer_time",
+            9 => "total_distance",
+            10 => "total_cycles",
+            11 => "total_calories",
+            14 => "avg_speed",
+            15 => "max_speed",
+            16 => "avg_heart_rate",
+            17 => "max_heart_rate",
+            18 => "avg_cadence",
+            19 => "max_cadence",
+            20 => "avg_power",
+            21 => "max_power",
+            22 => "total_ascent",
+            23 => "total_descent",
+            25 => "first_lap_index",
+            26 => "num_laps",
+            27 => "event_group",
+            28 => "trigger",
+            29 => "nec_lat",
+            30 => "nec_long",
+            31 => "swc_lat",
+            32 => "swc_long",
+            34 => "normalized_power",
+            59 => "total_moving_time",
+            64 => "min_heart_rate",
+            124 => "enhanced_avg_speed",
+            125 => "enhanced_max_speed",
+            _ => return common,
+        },
+        MessageType::Activity => match field_num {
+            0 => "total_timer_time",
+            1 => "num_sessions",
+            2 => "type",
+            3 => "event",
+            4 => "event_type",
+            5 => "local_timestamp",
+            6 => "event_group",
+            _ => return common,
+        },
+        _ => return common,
+    };
+    Some(name)
+}
+
+/// Units of the physical values of the fields `get_field_name` knows, as the SDK profile
+/// spells them. Positions are in degrees, as `Value::F32` holds them.
+pub fn get_field_units(message_type: MessageType, field_num: u8) -> Option<&'static str> {
+    let units = match get_field_name(message_type, field_num)? {
+        "position_lat"
+        | "position_long"
+        | "start_position_lat"
+        | "start_position_long"
+        | "end_position_lat"
+        | "end_position_long"
+        | "nec_lat"
+        | "nec_long"
+        | "swc_lat"
+        | "swc_long" => "degrees",
+        "altitude" | "enhanced_altitude" | "distance" | "total_distance" | "total_ascent"
+        | "total_descent" => "m",
+        "speed" | "enhanced_speed" | "avg_speed" | "max_speed" | "enhanced_avg_speed"
+        | "enhanced_max_speed" => "m/s",
+        "heart_rate" | "avg_heart_rate" | "max_heart_rate" | "min_heart_rate" => "bpm",
+        "cadence" | "fractional_cadence" | "avg_cadence" | "max_cadence" => "rpm",
+        "power" | "accumulated_power" | "avg_power" | "max_power" | "normalized_power" => "watts",
+        "calories" | "total_calories" => "kcal",
+        "total_elapsed_time" | "total_timer_time" | "total_moving_time" | "cum_operating_time" => {
+            "s"
+        }
+        "stance_time" => "ms",
+        "vertical_oscillation" => "mm",
+        "temperature" => "C",
+        "grade" | "stance_time_percent" => "%",
+        "battery_voltage" => "V",
+        "total_cycles" => "cycles",
+        _ => return None,
+    };
+    Some(units)
+}
@@ -1,6 +1,15 @@
+use alloc::string::String;
+use alloc::vec::Vec;
 use binrw::__private::Required;
-use binrw::{BinResult, BinWrite, BinWriterExt, Endian};
-use std::io::{Read, Seek, SeekFrom, Write};
+use binrw::io::{Read, Seek, SeekFrom, Write};
+use binrw::{BinResult, BinWrite, BinWriterExt, Endian, Error};
+
+pub(crate) fn invalid_data<S: Into<String>>(msg: S) -> Error {
+    Error::Io(binrw::io::Error::new(
+        binrw::io::ErrorKind::InvalidData,
+        msg.into(),
+    ))
+}
 
 pub fn skip_bytes<R>(map: &mut R, s: u8)
 where
@@ -1,4 +1,9 @@
+#[cfg(feature = "std")]
+use crate::builder::scaled_field;
 use crate::protocol::data_field::DataField;
+use crate::protocol::get_field_offset::get_field_offset_fn;
+use crate::protocol::get_field_scale::get_field_scale_fn;
+use crate::protocol::message_type::MessageType;
 use crate::protocol::value::Value;
 
 // Helper functions and macros for the merge_sessions function
@@ -11,6 +16,20 @@ macro_rules! update_field {
     };
 }
 
+/// Like `update_field!`, but appends the field when the message does not have it yet.
+/// `Fit::define_added_fields` then gives the message a definition that covers it.
+#[macro_export]
+macro_rules! put_field {
+    ($fields:expr, $field_num:expr, $value:expr) => {
+        match $fields.iter_mut().find(|f| f.field_num == $field_num) {
+            Some(field) => field.value = $value,
+            None => $fields.push($crate::protocol::data_field::DataField::new(
+                $field_num, $value,
+            )),
+        }
+    };
+}
+
 pub fn get_field_value(field_num: u8, values: &[DataField]) -> Option<Value> {
     values
         .iter()
@@ -18,6 +37,102 @@ pub fn get_field_value(field_num: u8, values: &[DataField]) -> Option<Value> {
         .and_then(|field| Some(field.value.clone()))
 }
 
+/// Looks up a timestamp field, returning the unix time it was decoded to.
+pub(crate) fn get_field_time(field_num: u8, values: &[DataField]) -> Option<u32> {
+    match get_field_value(field_num, values)? {
+        Value::Time(t) => Some(t),
+        _ => None,
+    }
+}
+
+/// Looks up `field_num` and converts it to its physical value, applying the scale and
+/// offset the profile defines for it. Invalid (all ones) values yield `None`.
+pub fn get_field_scaled(
+    message_type: MessageType,
+    field_num: u8,
+    values: &[DataField],
+) -> Option<f64> {
+    let field = values.iter().find(|field| field.field_num == field_num)?;
+    let mut value = field.value.as_f64()?;
+    if let Some(scale) = get_field_scale_fn(message_type)(field_num as usize) {
+        value /= scale as f64;
+    }
+    if let Some(offset) = get_field_offset_fn(message_type)(field_num as usize) {
+        value -= offset as f64;
+    }
+    Some(value)
+}
+
+/// Stores a physical value into an existing numeric field, applying the profile scale and
+/// offset. The field keeps its current integer width so it still matches its definition;
+/// missing and non numeric fields are left untouched.
+#[cfg(feature = "std")]
+pub(crate) fn set_field_scaled(
+    message_type: MessageType,
+    field_num: u8,
+    value: f64,
+    values: &mut [DataField],
+) {
+    let field = match values.iter_mut().find(|field| field.field_num == field_num) {
+        Some(field) => field,
+        None => return,
+    };
+    let mut raw = value;
+    if let Some(offset) = get_field_offset_fn(message_type)(field_num as usize) {
+        raw += offset as f64;
+    }
+    if let Some(scale) = get_field_scale_fn(message_type)(field_num as usize) {
+        raw *= scale as f64;
+    }
+    let raw = raw.round();
+    field.value = match field.value {
+        Value::U8(_) => Value::U8(raw.clamp(0.0, 254.0) as u8),
+        Value::I8(_) => Value::I8(raw.clamp(-127.0, 126.0) as i8),
+        Value::U16(_) => Value::U16(raw.clamp(0.0, 65_534.0) as u16),
+        Value::I16(_) => Value::I16(raw.clamp(-32_767.0, 32_766.0) as i16),
+        Value::U32(_) => Value::U32(raw.clamp(0.0, 4_294_967_294.0) as u32),
+        Value::I32(_) => Value::I32(raw.clamp(-2_147_483_647.0, 2_147_483_646.0) as i32),
+        _ => return,
+    };
+}
+
+/// Like `set_field_scaled`, but appends the field with the integer width of its profile
+/// type when the message does not have it yet.
+#[cfg(feature = "std")]
+pub(crate) fn put_field_scaled(
+    message_type: MessageType,
+    field_num: u8,
+    value: f64,
+    values: &mut Vec<DataField>,
+) {
+    match values.iter().any(|field| field.field_num == field_num) {
+        true => set_field_scaled(message_type, field_num, value, values),
+        false => values.push(scaled_field(message_type, field_num, value)),
+    }
+}
+
+/// Reads a latitude/longitude pair in degrees. Unset coordinates, which decode to 180°,
+/// yield `None`.
+#[cfg(feature = "std")]
+pub(crate) fn get_position(
+    lat_field: u8,
+    long_field: u8,
+    values: &[DataField],
+) -> Option<(f64, f64)> {
+    let lat = match get_field_value(lat_field, values)? {
+        Value::F32(v) => v as f64,
+        _ => return None,
+    };
+    let long = match get_field_value(long_field, values)? {
+        Value::F32(v) => v as f64,
+        _ => return None,
+    };
+    if lat.abs() > 90.0 || long.abs() >= 180.0 {
+        return None;
+    }
+    Some((lat, long))
+}
+
 #[macro_export]
 macro_rules! merge_stats {
     // Base case
@@ -106,34 +221,11 @@ macro_rules! merge_stats {
 
     // Average value case
     (avg $field_num:expr, $total_value:expr, $count:expr, $session:expr, $($rest:tt)*) => {
-         if let Some(value) = get_field_value($field_num, &$session.data.values) {
-            match value {
-                Value::U16(val) => {
-                    if let Value::I32(total_value) = $total_value {
-                        $total_value = Value::I32(total_value + (val as i32));
-                        $count = $count + 1;
-                    }
-                },
-                Value::I16(val) => {
-                    if let Value::I32(total_value) = $total_value {
-                        $total_value = Value::I32(total_value + (val as i32));
-                        $count = $count + 1;
-                    }
-                },
-                Value::U8(val) => {
-                    if let Value::I32(total_value) = $total_value {
-                        $total_value = Value::I32(total_value + (val as i32));
-                        $count = $count + 1;
-                    }
-                },
-                Value::I8(val) => {
-                    if let Value::I32(total_value) = $total_value {
-                        $total_value = Value::I32(total_value + (val as i32));
-                        $count = $count + 1;
-                    }
-                },
-                _ => {}
-            }
+        if let Some(value) = get_field_value($field_num, &$session.data.values)
+            .and_then(|value| value.as_i64())
+        {
+            $total_value += value;
+            $count += 1;
         }
         merge_stats!($($rest)*);
     };
@@ -1,25 +1,32 @@
-mod consts;
+pub mod base_type;
+pub(crate) mod consts;
 pub mod data_field;
-mod get_field_offset;
-mod get_field_scale;
-mod get_field_string_value;
-mod get_field_type;
+pub mod get_field_name;
+pub(crate) mod get_field_offset;
+pub(crate) mod get_field_scale;
+pub(crate) mod get_field_string_value;
+pub(crate) mod get_field_type;
 pub mod io;
 pub mod macros;
 pub mod message_type;
+pub mod time;
 pub mod value;
 
 use crate::protocol::consts::{
-    COMPRESSED_HEADER_LOCAL_MESSAGE_NUMBER_MASK, COMPRESSED_HEADER_MASK,
-    COMPRESSED_HEADER_TIME_OFFSET_MASK, CRC_TABLE, DEFINITION_HEADER_MASK, DEVELOPER_FIELDS_MASK,
-    FIELD_DEFINITION_BASE_ENDIAN, FIELD_DEFINITION_BASE_NUMBER, LOCAL_MESSAGE_NUMBER_MASK,
+    COMPRESSED_HEADER_LAST_TIMESTAMP_MASK, COMPRESSED_HEADER_LOCAL_MESSAGE_NUMBER_MASK,
+    COMPRESSED_HEADER_MASK, COMPRESSED_HEADER_TIME_OFFSET_MASK,
+    COMPRESSED_HEADER_TIME_OFFSET_ROLLOVER, CRC_TABLE, DEFINITION_HEADER_MASK,
+    DEVELOPER_FIELDS_MASK, FIELD_DEFINITION_BASE_ENDIAN, FIELD_DEFINITION_BASE_NUMBER,
+    LOCAL_MESSAGE_NUMBER_MASK,
 };
 use crate::protocol::data_field::DataField;
 use crate::protocol::get_field_string_value::FieldType;
 use crate::protocol::message_type::MessageType;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use binrw::io::{Seek, Write};
 use binrw::{binrw, BinRead, BinResult, BinWrite, Endian};
-use std::fmt::Debug;
-use std::io::{Seek, Write};
+use core::fmt::Debug;
 
 pub type MatchScaleFn = fn(usize) -> Option<f32>;
 pub type MatchOffsetFn = fn(usize) -> Option<i16>;
@@ -276,6 +283,21 @@ impl FitMessageHeader {
         }
     }
 
+    /// The time of a message with a compressed timestamp header, from the time of the last
+    /// message before it. The FIT epoch is a multiple of 32 seconds, so unix times roll over
+    /// at the same points FIT times do.
+    pub fn compressed_time(&self, last: u32) -> Option<u32> {
+        if !self.compressed_header {
+            return None;
+        }
+        let time_offset = self.time_offset? as u32;
+        let mut timestamp = (last & COMPRESSED_HEADER_LAST_TIMESTAMP_MASK) + time_offset;
+        if time_offset < last & COMPRESSED_HEADER_TIME_OFFSET_MASK as u32 {
+            timestamp += COMPRESSED_HEADER_TIME_OFFSET_ROLLOVER;
+        }
+        Some(timestamp)
+    }
+
     pub fn new(is_def: bool, local_num: u8) -> Self {
         Self {
             compressed_header: false,
@@ -0,0 +1,99 @@
+use alloc::string::String;
+use core::fmt::Write;
+
+/// Formats a unix timestamp (as stored in `Value::Time`) as an ISO 8601 UTC date time.
+pub fn format_time(unix: u32) -> String {
+    let days = (unix / 86_400) as i64;
+    let secs = unix % 86_400;
+    let (year, month, day) = civil_from_days(days);
+    let mut out = String::with_capacity(20);
+    let _ = write!(
+        out,
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    );
+    out
+}
+
+/// Parses an ISO 8601 date time such as `2023-09-29T05:49:21.000Z` or
+/// `2023-09-29T13:49:21+08:00` into a unix timestamp. Fractional seconds are dropped.
+pub fn parse_time(s: &str) -> Option<u32> {
+    let s = s.trim();
+    let (date, time) = s.split_once('T')?;
+    let mut date = date.splitn(3, '-');
+    let year: i64 = date.next()?.parse().ok()?;
+    let month: u32 = date.next()?.parse().ok()?;
+    let day: u32 = date.next()?.parse().ok()?;
+
+    let (time, offset) = match time.find(['Z', '+', '-']) {
+        Some(i) => time.split_at(i),
+        None => (time, ""),
+    };
+    let mut time = time.splitn(3, ':');
+    let hour: i64 = time.next()?.parse().ok()?;
+    let minute: i64 = time.next()?.parse().ok()?;
+    let second: f64 = time.next().unwrap_or("0").parse().ok()?;
+    let offset = match offset {
+        "" | "Z" => 0,
+        _ => {
+            let sign = if offset.starts_with('-') { -1 } else { 1 };
+            let (h, m) = offset[1..].split_once(':').unwrap_or((&offset[1..], "0"));
+            sign * (h.parse::<i64>().ok()? * 3600 + m.parse::<i64>().ok()? * 60)
+        }
+    };
+
+    let days = days_from_civil(year, month, day);
+    let unix = days * 86_400 + hour * 3600 + minute * 60 + second as i64 - offset;
+    u32::try_from(unix).ok()
+}
+
+// Howard Hinnant's civil-to-days algorithm, the inverse of `civil_from_days`.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+// Howard Hinnant's days-to-civil algorithm, valid for the whole u32 range.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::time::{format_time, parse_time};
+
+    #[test]
+    fn format_time_test() {
+        assert_eq!(format_time(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_time(631_065_600), "1989-12-31T00:00:00Z");
+        assert_eq!(format_time(1_695_966_561), "2023-09-29T05:49:21Z");
+    }
+
+    #[test]
+    fn parse_time_test() {
+        assert_eq!(parse_time("2023-09-29T05:49:21Z"), Some(1_695_966_561));
+        assert_eq!(parse_time("2023-09-29T05:49:21.250Z"), Some(1_695_966_561));
+        assert_eq!(parse_time("2023-09-29T13:49:21+08:00"), Some(1_695_966_561));
+        assert_eq!(parse_time("1989-12-31T00:00:00"), Some(631_065_600));
+        assert_eq!(parse_time("not a time"), None);
+    }
+}
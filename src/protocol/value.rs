@@ -1,3 +1,10 @@
+use crate::protocol::time::format_time;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+use core::fmt::{Display, Formatter};
+
 //////////
 //// Value
 //////////
@@ -23,33 +30,114 @@ pub enum Value {
     None,
 }
 
+impl Value {
+    /// The number held by a numeric variant or `Time`, or `None` for the invalid (all ones)
+    /// value of its base type. No scale or offset is applied.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::F32(v) if v.is_finite() => Some(v as f64),
+            Value::F64(v) if v.is_finite() => Some(v),
+            Value::F32(_) | Value::F64(_) => None,
+            Value::U64(v) if v != u64::MAX => Some(v as f64),
+            _ => self.as_i64().map(|v| v as f64),
+        }
+    }
+
+    /// The number held by an integer variant or `Time`, or `None` for floats, other
+    /// variants and invalid values.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::U8(v) if v != u8::MAX => Some(v as i64),
+            Value::I8(v) if v != i8::MAX => Some(v as i64),
+            Value::U16(v) if v != u16::MAX => Some(v as i64),
+            Value::I16(v) if v != i16::MAX => Some(v as i64),
+            Value::U32(v) if v != u32::MAX => Some(v as i64),
+            Value::I32(v) if v != i32::MAX => Some(v as i64),
+            Value::I64(v) if v != i64::MAX => Some(v),
+            Value::U64(v) if v != u64::MAX => i64::try_from(v).ok(),
+            Value::Time(v) => Some(v as i64),
+            _ => None,
+        }
+    }
+
+    /// Like `as_i64`, for non-negative values.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Value::U64(v) if v != u64::MAX => Some(v),
+            _ => self.as_i64().and_then(|v| u64::try_from(v).ok()),
+        }
+    }
+
+    /// The name of an `Enum` or the text of a `String`.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Enum(v) => Some(v),
+            Value::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Whether the value is set, i.e. not `None` nor the invalid value of its base type.
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Value::None => false,
+            Value::Enum(_)
+            | Value::String(_)
+            | Value::ArrU8(_)
+            | Value::ArrU16(_)
+            | Value::ArrU32(_) => true,
+            _ => self.as_f64().is_some(),
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::U8(_) => "u8",
+            Value::I8(_) => "i8",
+            Value::U16(_) => "u16",
+            Value::I16(_) => "i16",
+            Value::U32(_) => "u32",
+            Value::I32(_) => "i32",
+            Value::Enum(_) => "enum",
+            Value::String(_) => "string",
+            Value::F32(_) => "f32",
+            Value::F64(_) => "f64",
+            Value::I64(_) => "i64",
+            Value::U64(_) => "u64",
+            Value::Time(_) => "time",
+            Value::ArrU8(_) | Value::ArrU16(_) | Value::ArrU32(_) => "array",
+            Value::None => "none",
+        }
+    }
+}
+
 #[allow(unused_must_use)]
 impl Value {
     pub(super) fn rescale(&mut self, val: f32) {
         match self {
             Value::U8(mut inner) => {
                 let new_inner = f32::from(inner) * val;
-                std::mem::replace(&mut inner, new_inner as u8);
+                core::mem::replace(&mut inner, new_inner as u8);
             }
             Value::I8(mut inner) => {
                 let new_inner = f32::from(inner) * val;
-                std::mem::replace(&mut inner, new_inner as i8);
+                core::mem::replace(&mut inner, new_inner as i8);
             }
             Value::U16(mut inner) => {
                 let new_inner = f32::from(inner) * val;
-                std::mem::replace(&mut inner, new_inner as u16);
+                core::mem::replace(&mut inner, new_inner as u16);
             }
             Value::I16(mut inner) => {
                 let new_inner = f32::from(inner) * val;
-                std::mem::replace(&mut inner, new_inner as i16);
+                core::mem::replace(&mut inner, new_inner as i16);
             }
             Value::U32(mut inner) => {
                 let new_inner = inner as f32 * val;
-                std::mem::replace(&mut inner, new_inner as u32);
+                core::mem::replace(&mut inner, new_inner as u32);
             }
             Value::I32(mut inner) => {
                 let new_inner = inner as f32 * val;
-                std::mem::replace(&mut inner, new_inner as i32);
+                core::mem::replace(&mut inner, new_inner as i32);
             }
             _ => (),
         }
@@ -59,27 +147,27 @@ impl Value {
         match self {
             Value::U8(mut inner) => {
                 let new_inner = f32::from(inner) / val;
-                std::mem::replace(&mut inner, new_inner as u8);
+                core::mem::replace(&mut inner, new_inner as u8);
             }
             Value::I8(mut inner) => {
                 let new_inner = f32::from(inner) / val;
-                std::mem::replace(&mut inner, new_inner as i8);
+                core::mem::replace(&mut inner, new_inner as i8);
             }
             Value::U16(mut inner) => {
                 let new_inner = f32::from(inner) / val;
-                std::mem::replace(&mut inner, new_inner as u16);
+                core::mem::replace(&mut inner, new_inner as u16);
             }
             Value::I16(mut inner) => {
                 let new_inner = f32::from(inner) / val;
-                std::mem::replace(&mut inner, new_inner as i16);
+                core::mem::replace(&mut inner, new_inner as i16);
             }
             Value::U32(mut inner) => {
                 let new_inner = inner as f32 / val;
-                std::mem::replace(&mut inner, new_inner as u32);
+                core::mem::replace(&mut inner, new_inner as u32);
             }
             Value::I32(mut inner) => {
                 let new_inner = inner as f32 / val;
-                std::mem::replace(&mut inner, new_inner as i32);
+                core::mem::replace(&mut inner, new_inner as i32);
             }
             _ => (),
         }
@@ -88,27 +176,27 @@ impl Value {
         match self {
             Value::U8(mut inner) => {
                 let new_inner = i16::from(inner) - val;
-                std::mem::replace(&mut inner, new_inner as u8);
+                core::mem::replace(&mut inner, new_inner as u8);
             }
             Value::I8(mut inner) => {
                 let new_inner = i16::from(inner) - val;
-                std::mem::replace(&mut inner, new_inner as i8);
+                core::mem::replace(&mut inner, new_inner as i8);
             }
             Value::U16(mut inner) => {
                 let new_inner = inner as i16 - val;
-                std::mem::replace(&mut inner, new_inner as u16);
+                core::mem::replace(&mut inner, new_inner as u16);
             }
             Value::I16(mut inner) => {
                 let new_inner = inner - val;
-                std::mem::replace(&mut inner, new_inner);
+                core::mem::replace(&mut inner, new_inner);
             }
             Value::U32(mut inner) => {
                 let new_inner = inner as i16 - val;
-                std::mem::replace(&mut inner, new_inner as u32);
+                core::mem::replace(&mut inner, new_inner as u32);
             }
             Value::I32(mut inner) => {
                 let new_inner = inner as i16 - val;
-                std::mem::replace(&mut inner, i32::from(new_inner));
+                core::mem::replace(&mut inner, i32::from(new_inner));
             }
             _ => (),
         }
@@ -118,27 +206,27 @@ impl Value {
         match self {
             Value::U8(mut inner) => {
                 let new_inner = i16::from(inner) + val;
-                std::mem::replace(&mut inner, new_inner as u8);
+                core::mem::replace(&mut inner, new_inner as u8);
             }
             Value::I8(mut inner) => {
                 let new_inner = i16::from(inner) + val;
-                std::mem::replace(&mut inner, new_inner as i8);
+                core::mem::replace(&mut inner, new_inner as i8);
             }
             Value::U16(mut inner) => {
                 let new_inner = inner as i16 + val;
-                std::mem::replace(&mut inner, new_inner as u16);
+                core::mem::replace(&mut inner, new_inner as u16);
             }
             Value::I16(mut inner) => {
                 let new_inner = inner + val;
-                std::mem::replace(&mut inner, new_inner);
+                core::mem::replace(&mut inner, new_inner);
             }
             Value::U32(mut inner) => {
                 let new_inner = inner as i16 + val;
-                std::mem::replace(&mut inner, new_inner as u32);
+                core::mem::replace(&mut inner, new_inner as u32);
             }
             Value::I32(mut inner) => {
                 let new_inner = inner as i16 + val;
-                std::mem::replace(&mut inner, i32::from(new_inner));
+                core::mem::replace(&mut inner, i32::from(new_inner));
             }
             _ => (),
         }
@@ -227,3 +315,150 @@ impl From<Value> for String {
         }
     }
 }
+
+/// Why a `Value` could not be converted with `TryFrom`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueError {
+    /// The value is unset: `None` or the invalid value of its base type.
+    Invalid,
+    /// The value is of another kind, or out of range of the target type.
+    Mismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+}
+
+impl Display for ValueError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueError::Invalid => write!(f, "value is not set"),
+            ValueError::Mismatch { expected, found } => {
+                write!(f, "expected a {} value, found {}", expected, found)
+            }
+        }
+    }
+}
+
+impl Error for ValueError {}
+
+impl Value {
+    fn error(&self, expected: &'static str) -> ValueError {
+        match self.is_valid() {
+            true => ValueError::Mismatch {
+                expected,
+                found: self.type_name(),
+            },
+            false => ValueError::Invalid,
+        }
+    }
+}
+
+// Integers convert from any integer variant whose value fits the target type.
+macro_rules! try_from_integer {
+    ($($t:ty),+) => {
+        $(
+            impl TryFrom<&Value> for $t {
+                type Error = ValueError;
+
+                fn try_from(value: &Value) -> Result<Self, Self::Error> {
+                    let v = match value {
+                        Value::U64(_) => value.as_u64().and_then(|v| <$t>::try_from(v).ok()),
+                        _ => value.as_i64().and_then(|v| <$t>::try_from(v).ok()),
+                    };
+                    v.ok_or_else(|| value.error(stringify!($t)))
+                }
+            }
+        )+
+    };
+}
+
+try_from_integer!(u8, i8, u16, i16, u32, i32, u64, i64);
+
+impl TryFrom<&Value> for f64 {
+    type Error = ValueError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        value.as_f64().ok_or_else(|| value.error("f64"))
+    }
+}
+
+impl TryFrom<&Value> for f32 {
+    type Error = ValueError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::F32(v) if v.is_finite() => Ok(*v),
+            _ => Err(value.error("f32")),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for &'a str {
+    type Error = ValueError;
+
+    fn try_from(value: &'a Value) -> Result<Self, Self::Error> {
+        value.as_str().ok_or_else(|| value.error("string"))
+    }
+}
+
+/// Prints the decoded value: enum names, ISO 8601 times, numbers without scale or offset
+/// applied and `-` for unset values. `FitDataMessage` prints physical values with units.
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if !self.is_valid() {
+            return write!(f, "-");
+        }
+        match self {
+            Value::U8(v) => write!(f, "{}", v),
+            Value::I8(v) => write!(f, "{}", v),
+            Value::U16(v) => write!(f, "{}", v),
+            Value::I16(v) => write!(f, "{}", v),
+            Value::U32(v) => write!(f, "{}", v),
+            Value::I32(v) => write!(f, "{}", v),
+            Value::U64(v) => write!(f, "{}", v),
+            Value::I64(v) => write!(f, "{}", v),
+            Value::F32(v) => write!(f, "{}", v),
+            Value::F64(v) => write!(f, "{}", v),
+            Value::Enum(v) => write!(f, "{}", v),
+            Value::String(v) => write!(f, "{}", v),
+            Value::Time(v) => write!(f, "{}", format_time(*v)),
+            Value::ArrU8(v) => write!(f, "{:?}", v),
+            Value::ArrU16(v) => write!(f, "{:?}", v),
+            Value::ArrU32(v) => write!(f, "{:?}", v),
+            Value::None => write!(f, "-"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::value::{Value, ValueError};
+
+    #[test]
+    fn value_conversion_test() {
+        assert_eq!(Value::U16(250).as_f64(), Some(250.0));
+        assert_eq!(Value::U16(u16::MAX).as_f64(), None);
+        assert_eq!(Value::I8(-5).as_i64(), Some(-5));
+        assert_eq!(Value::I8(-5).as_u64(), None);
+        assert_eq!(Value::Enum("running").as_str(), Some("running"));
+        assert!(!Value::U8(u8::MAX).is_valid());
+        assert!(Value::ArrU8(vec![]).is_valid());
+
+        assert_eq!(i32::try_from(&Value::U8(7)), Ok(7));
+        assert_eq!(u8::try_from(&Value::U16(300)).ok(), None);
+        assert_eq!(u8::try_from(&Value::U8(u8::MAX)), Err(ValueError::Invalid));
+        assert_eq!(
+            <&str>::try_from(&Value::U32(1)),
+            Err(ValueError::Mismatch {
+                expected: "string",
+                found: "u32"
+            })
+        );
+
+        assert_eq!(
+            Value::Time(1_000_000_000).to_string(),
+            "2001-09-09T01:46:40Z"
+        );
+        assert_eq!(Value::I32(i32::MAX).to_string(), "-");
+    }
+}
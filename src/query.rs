@@ -0,0 +1,117 @@
+use crate::protocol::get_field_name::{get_field_name, get_field_units};
+use crate::protocol::macros::{get_field_scaled, get_field_time};
+use crate::protocol::message_type::MessageType;
+use crate::protocol::value::Value;
+use crate::protocol::{FitDataMessage, FitMessage};
+use crate::Fit;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+impl Fit {
+    /// All data messages, in file order.
+    pub fn data_messages(&self) -> impl Iterator<Item = &FitDataMessage> {
+        self.data.iter().filter_map(|message| match message {
+            FitMessage::Data(msg) => Some(msg),
+            FitMessage::Definition(_) => None,
+        })
+    }
+
+    /// The data messages of one type, in file order.
+    pub fn messages(&self, message_type: MessageType) -> impl Iterator<Item = &FitDataMessage> {
+        self.data_messages()
+            .filter(move |msg| msg.data.message_type == message_type)
+    }
+
+    pub fn first(&self, message_type: MessageType) -> Option<&FitDataMessage> {
+        self.messages(message_type).next()
+    }
+
+    /// The data messages with a timestamp within `start..=end`, as unix times.
+    pub fn between(&self, start: u32, end: u32) -> impl Iterator<Item = &FitDataMessage> {
+        self.data_messages()
+            .filter(move |msg| msg.timestamp().is_some_and(|t| (start..=end).contains(&t)))
+    }
+}
+
+impl FitDataMessage {
+    /// The value of a field as decoded, without scale or offset applied.
+    pub fn get(&self, field_num: u8) -> Option<&Value> {
+        self.data
+            .values
+            .iter()
+            .find(|field| field.field_num == field_num)
+            .map(|field| &field.value)
+    }
+
+    /// The physical value of a numeric field, with the profile scale and offset applied.
+    /// Invalid values yield `None`.
+    pub fn get_f64(&self, field_num: u8) -> Option<f64> {
+        get_field_scaled(self.data.message_type, field_num, &self.data.values)
+    }
+
+    /// The `timestamp` field as a unix time.
+    pub fn timestamp(&self) -> Option<u32> {
+        get_field_time(253, &self.data.values)
+    }
+}
+
+/// Prints the message type and its fields by name, with physical values and their units,
+/// e.g. `Record { timestamp: 2023-09-29T05:49:21Z, heart_rate: 142 bpm }`.
+impl Display for FitDataMessage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let message_type = self.data.message_type;
+        write!(f, "{:?} {{", message_type)?;
+        for (i, field) in self.data.values.iter().enumerate() {
+            let separator = if i > 0 { "," } else { "" };
+            match get_field_name(message_type, field.field_num) {
+                Some(name) => write!(f, "{} {}: ", separator, name)?,
+                None => write!(f, "{} {}: ", separator, field.field_num)?,
+            }
+            let value = match field.value {
+                Value::Time(_) | Value::Enum(_) | Value::String(_) => None,
+                _ => self.get_f64(field.field_num),
+            };
+            match (value, get_field_units(message_type, field.field_num)) {
+                (Some(value), Some(units)) => write!(f, "{} {}", value, units)?,
+                (Some(value), None) => write!(f, "{}", value)?,
+                (None, _) => write!(f, "{}", field.value)?,
+            }
+        }
+        write!(f, " }}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::data_field::DataField;
+    use crate::protocol::message_type::MessageType;
+    use crate::protocol::value::Value;
+    use crate::test_util::recording;
+    use crate::Fit;
+
+    #[test]
+    fn query_test() {
+        let builder = recording(0..5, |t| {
+            vec![DataField::new(6, Value::U16(1000 * t as u16))]
+        });
+        let fit = Fit::read(builder.build().to_bytes().unwrap()).unwrap();
+
+        assert_eq!(fit.messages(MessageType::Record).count(), 5);
+        let file_id = fit.first(MessageType::FileId).unwrap();
+        assert_eq!(file_id.get(0), Some(&Value::Enum("activity")));
+        assert!(fit.first(MessageType::Session).is_none());
+
+        let speeds: Vec<_> = fit
+            .between(1_000_000_001, 1_000_000_002)
+            .filter_map(|msg| msg.get_f64(6))
+            .collect();
+        assert_eq!(speeds, vec![1.0, 2.0]);
+        assert_eq!(
+            fit.messages(MessageType::Record)
+                .nth(3)
+                .unwrap()
+                .to_string(),
+            "Record { timestamp: 2001-09-09T01:46:43Z, speed: 3 m/s }"
+        );
+    }
+}
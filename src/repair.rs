@@ -0,0 +1,288 @@
+use crate::builder::FitBuilder;
+use crate::protocol::data_field::DataField;
+use crate::protocol::io::invalid_data;
+use crate::protocol::macros::{get_field_time, get_field_value};
+use crate::protocol::message_type::MessageType;
+use crate::protocol::value::Value;
+use crate::protocol::{
+    calculate_fit_crc, DataMessage, DefinitionMessage, FitDataMessage, FitDefinitionMessage,
+    FitHeader, FitMessage, FitMessageHeader,
+};
+use crate::{is_timer_stop, Fit};
+use binrw::{BinReaderExt, BinResult};
+use std::io::Cursor;
+
+/// What `Fit::repair` had to fix.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RepairReport {
+    /// The header `data_size` did not match the messages that could be read.
+    pub data_size_mismatch: bool,
+    /// The file CRC was missing or wrong.
+    pub bad_crc: bool,
+    /// Bytes dropped after the last complete message.
+    pub discarded_bytes: usize,
+    /// Closing messages synthesised from the records, in the order they were added.
+    pub added: Vec<MessageType>,
+}
+
+impl RepairReport {
+    pub fn is_clean(&self) -> bool {
+        *self == RepairReport::default()
+    }
+}
+
+impl Fit {
+    /// Recovers what it can from a truncated or corrupted file.
+    ///
+    /// Messages are read up to the first one that cannot be decoded, the rest is discarded.
+    /// A file that ends without a timer stop, a `Lap` and `Session` covering the last records
+    /// or an `Activity` gets them synthesised, with summaries computed from the records.
+    /// Sizes and CRCs are recomputed when the result is written.
+    pub fn repair(buf: Vec<u8>) -> BinResult<(Fit, RepairReport)> {
+        let mut report = RepairReport::default();
+        let mut cursor = Cursor::new(&buf[..]);
+        let header: FitHeader = cursor.read_ne()?;
+        if header.data_type != ".FIT" {
+            return Err(invalid_data("not a FIT file"));
+        }
+        let header_size = header.header_size as usize;
+        let declared_end = header_size + header.data_size as usize;
+        let crc_ok = buf.len() >= declared_end + 2 && {
+            let crc = u16::from_le_bytes([buf[declared_end], buf[declared_end + 1]]);
+            // Accept a CRC over the whole file as well as one over the data only.
+            calculate_fit_crc(&buf[..declared_end]) == crc
+                || calculate_fit_crc(&buf[header_size..declared_end]) == crc
+        };
+        // A complete file with a bad CRC still ends at `data_size`, only a truncated one is
+        // read to the end of the buffer.
+        let end = match buf.len() >= declared_end + 2 {
+            true => declared_end,
+            false => buf.len(),
+        };
+        report.bad_crc = !crc_ok;
+        // Read only up to `end`, so that no message runs over into the CRC or past a
+        // `data_size` that ends inside it.
+        let mut cursor = Cursor::new(&buf[..end]);
+        cursor.set_position(header_size as u64);
+
+        let mut definitions: Vec<FitDefinitionMessage> = vec![];
+        let mut data = vec![];
+        let mut good = header_size;
+        while (cursor.position() as usize) < end {
+            match read_message(&mut cursor, &definitions) {
+                Ok(Some(FitMessage::Definition(def))) => {
+                    definitions.retain(|d| d.header.local_num != def.header.local_num);
+                    definitions.push(def.clone());
+                    data.push(FitMessage::Definition(def));
+                }
+                Ok(Some(message)) => data.push(message),
+                Ok(None) => {}
+                Err(_) => break,
+            }
+            good = cursor.position() as usize;
+        }
+        report.discarded_bytes = end.saturating_sub(good);
+        report.data_size_mismatch = good != declared_end;
+
+        let mut fit = Fit {
+            header: FitHeader {
+                data_size: (good - header_size) as u32,
+                ..header
+            },
+            data,
+        };
+        fit.close(&mut report);
+        Ok((fit, report))
+    }
+
+    /// Appends the timer stop, `Lap`, `Session` and `Activity` a complete activity ends with.
+    fn close(&mut self, report: &mut RepairReport) {
+        let mut records = vec![];
+        let mut last_lap = None;
+        let mut last_session = None;
+        let mut has_activity = false;
+        let mut sport = None;
+        for message in &self.data {
+            if let FitMessage::Data(msg) = message {
+                let values = &msg.data.values;
+                let time = get_field_time(253, values);
+                match msg.data.message_type {
+                    MessageType::Record => records.extend(time),
+                    MessageType::Lap => last_lap = last_lap.max(time),
+                    MessageType::Session => last_session = last_session.max(time),
+                    MessageType::Activity => has_activity = true,
+                    MessageType::Sport => sport = get_field_value(0, values),
+                    _ => {}
+                }
+            }
+        }
+        let (first, last) = match (records.iter().min(), records.iter().max()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return,
+        };
+
+        let mut builder = FitBuilder::new();
+        if !self.timer_events().last().is_some_and(|t| is_timer_stop(t)) {
+            builder.push_timer_event(last, "stop_all");
+            report.added.push(MessageType::Event);
+        }
+        if last_lap.is_none_or(|lap| lap < last) {
+            let start = last_lap.unwrap_or(first);
+            let mut lap = summary(start, last);
+            lap.extend([
+                DataField::new(0, Value::Enum("lap")),
+                DataField::new(1, Value::Enum("stop")),
+                DataField::new(24, Value::Enum("session_end")),
+                DataField::new(254, Value::U16(0)),
+            ]);
+            builder.push(MessageType::Lap, lap);
+            report.added.push(MessageType::Lap);
+        }
+        if last_session.is_none_or(|session| session < last) {
+            let start = last_session.unwrap_or(first);
+            let mut session = summary(start, last);
+            session.extend([
+                DataField::new(0, Value::Enum("session")),
+                DataField::new(1, Value::Enum("stop")),
+                DataField::new(5, sport.unwrap_or(Value::Enum("generic"))),
+                DataField::new(25, Value::U16(0)),
+                DataField::new(26, Value::U16(0)),
+                DataField::new(28, Value::Enum("activity_end")),
+                DataField::new(254, Value::U16(0)),
+            ]);
+            builder.push(MessageType::Session, session);
+            report.added.push(MessageType::Session);
+        }
+        if !has_activity {
+            builder.push(
+                MessageType::Activity,
+                vec![
+                    DataField::new(253, Value::Time(last)),
+                    DataField::new(0, Value::U32(0)),
+                    DataField::new(1, Value::U16(0)),
+                    DataField::new(2, Value::Enum("manual")),
+                    DataField::new(3, Value::Enum("activity")),
+                    DataField::new(4, Value::Enum("stop")),
+                ],
+            );
+            report.added.push(MessageType::Activity);
+        }
+        if report.added.is_empty() {
+            return;
+        }
+        self.data.extend(builder.build().data);
+        self.number_laps();
+        self.recompute_summaries();
+        self.update_activity(last);
+    }
+}
+
+/// The times of a summary message spanning `start..=end`. `Fit::recompute_summaries` adds
+/// the statistics the records supply.
+fn summary(start: u32, end: u32) -> Vec<DataField> {
+    vec![
+        DataField::new(253, Value::Time(end)),
+        DataField::new(2, Value::Time(start)),
+    ]
+}
+
+pub(crate) fn read_message(
+    cursor: &mut Cursor<&[u8]>,
+    definitions: &[FitDefinitionMessage],
+) -> BinResult<Option<FitMessage>> {
+    let header: FitMessageHeader = cursor.read_ne()?;
+    if header.definition {
+        if header.dev_fields {
+            return Err(invalid_data("developer fields are not supported"));
+        }
+        let data: DefinitionMessage = cursor.read_ne_args((header.dev_fields,))?;
+        return Ok(Some(FitMessage::Definition(FitDefinitionMessage {
+            header,
+            data,
+        })));
+    }
+    let definition = definitions
+        .iter()
+        .find(|def| def.header.local_num == header.local_num)
+        .ok_or_else(|| invalid_data("data message without a definition"))?;
+    // The field readers do not fail at the end of input, so check a whole message is left
+    // before the end of the cursor.
+    let size: usize = definition.data.fields.iter().map(|f| f.size as usize).sum();
+    if cursor.get_ref().len() - (cursor.position() as usize) < size {
+        return Err(invalid_data("partial data message"));
+    }
+    let data: DataMessage = cursor.read_ne_args((definition,))?;
+    if data.message_type == MessageType::None {
+        return Ok(None);
+    }
+    Ok(Some(FitMessage::Data(FitDataMessage { header, data })))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::calculate_fit_crc;
+    use crate::protocol::data_field::DataField;
+    use crate::protocol::message_type::MessageType;
+    use crate::protocol::value::Value;
+    use crate::test_util::{activity_builder, push_records, START};
+    use crate::Fit;
+
+    #[test]
+    fn repair_test() {
+        let mut builder = activity_builder();
+        builder.push_timer_event(START, "start");
+        push_records(&mut builder, 0..=60, |_| {
+            vec![DataField::new(3, Value::U8(130))]
+        });
+        let complete = builder.build().to_bytes().unwrap();
+
+        // A `data_size` that ends inside the last record, with a CRC that matches it.
+        let mut buf = complete.clone();
+        let data_size = u32::from_le_bytes(buf[4..8].try_into().unwrap()) - 1;
+        buf[4..8].copy_from_slice(&data_size.to_le_bytes());
+        let end = buf.len() - 3;
+        let crc = calculate_fit_crc(&buf[..end]);
+        buf.truncate(end);
+        buf.extend(crc.to_le_bytes());
+        let (_, report) = Fit::repair(buf).unwrap();
+        assert!(!report.bad_crc);
+        assert!(report.data_size_mismatch);
+        assert_eq!(report.discarded_bytes, 5);
+
+        // A complete file whose CRC has one bit flipped.
+        let mut buf = complete.clone();
+        let last = buf.len() - 1;
+        buf[last] ^= 0x01;
+        let (fit, report) = Fit::repair(buf).unwrap();
+        assert!(report.bad_crc);
+        assert!(!report.data_size_mismatch);
+        assert_eq!(report.discarded_bytes, 0);
+        assert_eq!(fit.messages(MessageType::Record).count(), 61);
+
+        let mut buf = complete;
+        // Lose the CRC and half of the last record.
+        buf.truncate(buf.len() - 5);
+
+        let (fit, report) = Fit::repair(buf).unwrap();
+        assert!(report.bad_crc);
+        assert!(report.data_size_mismatch);
+        assert_eq!(report.discarded_bytes, 3);
+        assert_eq!(
+            report.added,
+            vec![
+                MessageType::Event,
+                MessageType::Lap,
+                MessageType::Session,
+                MessageType::Activity
+            ]
+        );
+
+        let fit = Fit::read(fit.to_bytes().unwrap()).unwrap();
+        let session = fit.first(MessageType::Session).unwrap();
+        assert_eq!(session.get_f64(8), Some(59.0));
+        assert_eq!(session.get_f64(16), Some(130.0));
+        // The records have no power, so neither does the synthesised session.
+        assert_eq!(session.get(20), None);
+        assert_eq!(session.get(34), None);
+    }
+}
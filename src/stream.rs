@@ -0,0 +1,207 @@
+use crate::lazy::{read_layout, LazyMessage, OwnedLayout};
+use crate::protocol::io::invalid_data;
+use crate::protocol::message_type::MessageType;
+use crate::protocol::{
+    DefinitionMessage, FitDefinitionMessage, FitHeader, FitMessage, FitMessageHeader,
+};
+use crate::Fit;
+use binrw::{BinReaderExt, BinResult};
+use std::io::Cursor;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const LOCAL_MESSAGE_COUNT: usize = 16;
+
+/// Decodes a file message by message as its bytes arrive, reading no more than the message
+/// at hand. Readers from the `futures` crate can be adapted with `tokio_util::compat`.
+///
+/// Every message is read in a few small `read_exact` calls, so unbuffered sources such as
+/// files and sockets are best wrapped in a `tokio::io::BufReader`.
+pub struct FitReader<R> {
+    reader: R,
+    header: FitHeader,
+    /// Layout of the definition message last given to each local message number.
+    layouts: [Option<OwnedLayout>; LOCAL_MESSAGE_COUNT],
+    position: u64,
+    end: u64,
+    done: bool,
+}
+
+impl<R: AsyncRead + Unpin> FitReader<R> {
+    /// Reads the file header.
+    pub async fn new(mut reader: R) -> BinResult<Self> {
+        let mut buf = vec![0; 12];
+        reader.read_exact(&mut buf).await?;
+        let header_size = buf[0] as usize;
+        if header_size < buf.len() {
+            return Err(invalid_data("FIT header is shorter than 12 bytes"));
+        }
+        if header_size > buf.len() {
+            let start = buf.len();
+            buf.resize(header_size, 0);
+            reader.read_exact(&mut buf[start..]).await?;
+        }
+        let header: FitHeader = Cursor::new(&buf).read_ne()?;
+        if header.data_type != ".FIT" {
+            return Err(invalid_data("not a FIT file"));
+        }
+        Ok(FitReader {
+            reader,
+            position: header_size as u64,
+            end: header_size as u64 + header.data_size as u64,
+            header,
+            layouts: Default::default(),
+            done: false,
+        })
+    }
+
+    pub fn header(&self) -> &FitHeader {
+        &self.header
+    }
+
+    /// The next message, the same as `Fit::read` would give, or `None` once the data and the
+    /// file CRC after it have been read. The CRC is not checked.
+    pub async fn next_message(&mut self) -> BinResult<Option<FitMessage>> {
+        loop {
+            if self.position >= self.end {
+                if !self.done {
+                    let mut crc = [0; 2];
+                    self.reader.read_exact(&mut crc).await?;
+                    self.done = true;
+                }
+                return Ok(None);
+            }
+            let offset = self.position as usize;
+            let mut bytes = Vec::with_capacity(1);
+            self.read_more(&mut bytes, 1).await?;
+            let header: FitMessageHeader = Cursor::new(&bytes).read_ne()?;
+            let local = header.local_num as usize;
+            if header.definition {
+                self.read_more(&mut bytes, 5).await?;
+                let num_fields = bytes[5] as usize;
+                self.read_more(&mut bytes, num_fields * 3).await?;
+                if header.dev_fields {
+                    self.read_more(&mut bytes, 1).await?;
+                    let count = bytes[bytes.len() - 1] as usize;
+                    self.read_more(&mut bytes, count * 3).await?;
+                }
+                let data: DefinitionMessage =
+                    Cursor::new(&bytes[1..]).read_ne_args((header.dev_fields,))?;
+                self.layouts[local] = Some(read_layout(&bytes, 0, header.dev_fields)?.to_owned());
+                return Ok(Some(FitMessage::Definition(FitDefinitionMessage {
+                    header,
+                    data,
+                })));
+            }
+
+            let size = self.layout(local)?.size;
+            self.read_more(&mut bytes, size).await?;
+            let layout = self.layout(local)?.as_layout();
+            let msg = LazyMessage::new(header, offset, layout, &bytes[1..]);
+            if msg.message_type != MessageType::None {
+                return Ok(Some(FitMessage::Data(msg.decode())));
+            }
+        }
+    }
+
+    /// Reads the remaining messages into a `Fit`.
+    pub async fn read_to_end(mut self) -> BinResult<Fit> {
+        let mut data = vec![];
+        while let Some(message) = self.next_message().await? {
+            data.push(message);
+        }
+        Ok(Fit {
+            header: self.header,
+            data,
+        })
+    }
+
+    /// The underlying reader, positioned after the last message read.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn layout(&self, local: usize) -> BinResult<&OwnedLayout> {
+        self.layouts[local]
+            .as_ref()
+            .ok_or_else(|| invalid_data("data message without a definition"))
+    }
+
+    async fn read_more(&mut self, buf: &mut Vec<u8>, count: usize) -> BinResult<()> {
+        let start = buf.len();
+        buf.resize(start + count, 0);
+        self.reader.read_exact(&mut buf[start..]).await?;
+        self.position += count as u64;
+        Ok(())
+    }
+}
+
+/// Writes files to an asynchronous writer.
+pub struct FitWriter<W> {
+    writer: W,
+}
+
+impl<W: AsyncWrite + Unpin> FitWriter<W> {
+    pub fn new(writer: W) -> Self {
+        FitWriter { writer }
+    }
+
+    /// Encodes the file, recomputing the data size and CRCs, and writes it out. The header
+    /// carries the data size and the CRC covers all of the data, so the file is encoded into
+    /// memory in full before the first byte is written.
+    pub async fn write(&mut self, fit: &Fit) -> BinResult<()> {
+        let buf = fit.to_bytes()?;
+        self.writer.write_all(&buf).await?;
+        self.writer.flush().await?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::{FitReader, FitWriter};
+    use crate::test_util::{heart_rate, recording};
+    use crate::Fit;
+
+    #[tokio::test]
+    async fn stream_test() {
+        let buf = recording(0..3, heart_rate).build().to_bytes().unwrap();
+
+        // Two files back to back: the reader stops at the end of the first.
+        let stream = [buf.clone(), buf.clone()].concat();
+        let mut reader = FitReader::new(&stream[..]).await.unwrap();
+        let mut count = 0;
+        while reader.next_message().await.unwrap().is_some() {
+            count += 1;
+        }
+        assert_eq!(count, 6);
+        assert_eq!(reader.into_inner(), &buf[..]);
+
+        let fit = FitReader::new(&buf[..])
+            .await
+            .unwrap()
+            .read_to_end()
+            .await
+            .unwrap();
+        let expected = Fit::read(buf.clone()).unwrap();
+        assert_eq!(fit.to_bytes().unwrap(), expected.to_bytes().unwrap());
+
+        let mut writer = FitWriter::new(vec![]);
+        writer.write(&fit).await.unwrap();
+        assert_eq!(writer.into_inner(), buf);
+
+        let mut short = buf.clone();
+        short[0] = 11;
+        assert!(FitReader::new(&short[..]).await.is_err());
+
+        assert!(FitReader::new(&buf[..buf.len() - 3])
+            .await
+            .unwrap()
+            .read_to_end()
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,385 @@
+use crate::geo::haversine;
+use crate::protocol::data_field::DataField;
+use crate::protocol::macros::{
+    get_field_scaled, get_field_time, get_field_value, get_position, put_field_scaled,
+};
+use crate::protocol::message_type::MessageType;
+use crate::protocol::value::Value;
+use crate::protocol::FitMessage;
+use crate::Fit;
+
+/// Below this speed in m/s a record does not count towards the moving time.
+const MOVING_SPEED: f64 = 0.5;
+/// Window of the rolling power average behind normalized power, in seconds.
+const NORMALIZED_POWER_WINDOW: usize = 30;
+
+// Field numbers of each statistic in `Summary::values` order.
+pub(crate) const LAP_FIELDS: [u8; 19] = [
+    7, 8, 9, 11, 13, 14, 110, 111, 15, 16, 63, 17, 18, 19, 20, 21, 22, 33, 52,
+];
+pub(crate) const SESSION_FIELDS: [u8; 19] = [
+    7, 8, 9, 11, 14, 15, 124, 125, 16, 17, 64, 18, 19, 20, 21, 22, 23, 34, 59,
+];
+
+struct Sample {
+    time: u32,
+    position: Option<(f64, f64)>,
+    distance: Option<f64>,
+    speed: Option<f64>,
+    altitude: Option<f64>,
+    heart_rate: Option<f64>,
+    cadence: Option<f64>,
+    power: Option<f64>,
+    calories: Option<f64>,
+}
+
+impl Sample {
+    fn from_record(values: &[DataField]) -> Option<Self> {
+        let scaled = |field_num| get_field_scaled(MessageType::Record, field_num, values);
+        Some(Sample {
+            time: get_field_time(253, values)?,
+            position: get_position(0, 1, values),
+            distance: scaled(5),
+            speed: scaled(73).or_else(|| scaled(6)),
+            altitude: scaled(78).or_else(|| scaled(2)),
+            heart_rate: scaled(3),
+            cadence: scaled(4),
+            power: scaled(7),
+            calories: scaled(33),
+        })
+    }
+}
+
+#[derive(Default)]
+struct Summary {
+    elapsed_time: Option<f64>,
+    timer_time: Option<f64>,
+    distance: Option<f64>,
+    calories: Option<f64>,
+    avg_speed: Option<f64>,
+    max_speed: Option<f64>,
+    avg_heart_rate: Option<f64>,
+    max_heart_rate: Option<f64>,
+    min_heart_rate: Option<f64>,
+    avg_cadence: Option<f64>,
+    max_cadence: Option<f64>,
+    avg_power: Option<f64>,
+    max_power: Option<f64>,
+    ascent: Option<f64>,
+    descent: Option<f64>,
+    normalized_power: Option<f64>,
+    moving_time: Option<f64>,
+}
+
+impl Summary {
+    /// `samples` must be sorted by time, `running[i]` tells whether the timer ran between
+    /// sample `i` and `i + 1`.
+    fn new(samples: &[&Sample], running: &[bool]) -> Self {
+        let (first, last) = match (samples.first(), samples.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Summary::default(),
+        };
+        // Seconds each sample's values stay in effect while the timer runs.
+        let weights: Vec<f64> = samples
+            .windows(2)
+            .zip(running)
+            .map(|(pair, running)| match running {
+                true => (pair[1].time - pair[0].time) as f64,
+                false => 0.0,
+            })
+            .chain(Some(0.0))
+            .collect();
+        let timer_time: f64 = weights.iter().sum();
+
+        let distance = match (
+            samples.iter().find_map(|s| s.distance),
+            samples.iter().rev().find_map(|s| s.distance),
+        ) {
+            (Some(start), Some(end)) => Some(end - start),
+            _ => {
+                let positions: Vec<_> = samples.iter().filter_map(|s| s.position).collect();
+                (positions.len() > 1).then(|| {
+                    positions
+                        .windows(2)
+                        .map(|pair| haversine(pair[0], pair[1]))
+                        .sum()
+                })
+            }
+        };
+
+        let mut moving_time = 0.0;
+        for (i, pair) in samples.windows(2).enumerate() {
+            let dt = weights[i];
+            let speed = pair[0].speed.or_else(|| {
+                let d = pair[1].distance? - pair[0].distance?;
+                (dt > 0.0).then(|| d / dt)
+            });
+            if speed.is_some_and(|speed| speed >= MOVING_SPEED) {
+                moving_time += dt;
+            }
+        }
+
+        let (mut ascent, mut descent) = (None, None);
+        let altitudes: Vec<f64> = samples.iter().filter_map(|s| s.altitude).collect();
+        for pair in altitudes.windows(2) {
+            let diff = pair[1] - pair[0];
+            *ascent.get_or_insert(0.0) += diff.max(0.0);
+            *descent.get_or_insert(0.0) += (-diff).max(0.0);
+        }
+
+        let calories = match (
+            samples.iter().find_map(|s| s.calories),
+            samples.iter().rev().find_map(|s| s.calories),
+        ) {
+            (Some(start), Some(end)) => Some(end - start),
+            _ => None,
+        };
+
+        let heart_rate = Channel::new(samples, &weights, |s| s.heart_rate);
+        let cadence = Channel::new(samples, &weights, |s| s.cadence);
+        let power = Channel::new(samples, &weights, |s| s.power);
+        let speed = Channel::new(samples, &weights, |s| s.speed);
+
+        Summary {
+            elapsed_time: Some((last.time - first.time) as f64),
+            timer_time: Some(timer_time),
+            avg_speed: match distance {
+                Some(distance) if timer_time > 0.0 => Some(distance / timer_time),
+                _ => speed.avg,
+            },
+            distance,
+            calories,
+            max_speed: speed.max,
+            avg_heart_rate: heart_rate.avg,
+            max_heart_rate: heart_rate.max,
+            min_heart_rate: heart_rate.min,
+            avg_cadence: cadence.avg,
+            max_cadence: cadence.max,
+            avg_power: power.avg,
+            max_power: power.max,
+            ascent,
+            descent,
+            normalized_power: normalized_power(samples, &weights),
+            moving_time: Some(moving_time),
+        }
+    }
+
+    fn values(&self) -> [Option<f64>; 19] {
+        [
+            self.elapsed_time,
+            self.timer_time,
+            self.distance,
+            self.calories,
+            self.avg_speed,
+            self.max_speed,
+            self.avg_speed,
+            self.max_speed,
+            self.avg_heart_rate,
+            self.max_heart_rate,
+            self.min_heart_rate,
+            self.avg_cadence,
+            self.max_cadence,
+            self.avg_power,
+            self.max_power,
+            self.ascent,
+            self.descent,
+            self.normalized_power,
+            self.moving_time,
+        ]
+    }
+}
+
+/// Time weighted average plus extremes of one record field.
+struct Channel {
+    avg: Option<f64>,
+    max: Option<f64>,
+    min: Option<f64>,
+}
+
+impl Channel {
+    fn new(samples: &[&Sample], weights: &[f64], value: impl Fn(&Sample) -> Option<f64>) -> Self {
+        let (mut sum, mut weight, mut count, mut plain_sum) = (0.0, 0.0, 0, 0.0);
+        let (mut max, mut min): (Option<f64>, Option<f64>) = (None, None);
+        for (sample, w) in samples.iter().zip(weights) {
+            if let Some(v) = value(sample) {
+                sum += v * w;
+                weight += w;
+                plain_sum += v;
+                count += 1;
+                max = Some(max.map_or(v, |m| m.max(v)));
+                min = Some(min.map_or(v, |m| m.min(v)));
+            }
+        }
+        let avg = if weight > 0.0 {
+            Some(sum / weight)
+        } else {
+            (count > 0).then(|| plain_sum / count as f64)
+        };
+        Channel { avg, max, min }
+    }
+}
+
+/// Fourth root of the mean fourth power of the 30 second rolling average power, resampled to
+/// one second. Needs at least one full window of power data.
+fn normalized_power(samples: &[&Sample], weights: &[f64]) -> Option<f64> {
+    let mut seconds = vec![];
+    for (sample, weight) in samples.iter().zip(weights) {
+        if let Some(power) = sample.power {
+            seconds.extend(std::iter::repeat_n(power, *weight as usize));
+        }
+    }
+    if seconds.len() < NORMALIZED_POWER_WINDOW {
+        return None;
+    }
+    let mut window: f64 = seconds[..NORMALIZED_POWER_WINDOW].iter().sum();
+    let mut total = (window / NORMALIZED_POWER_WINDOW as f64).powi(4);
+    for i in NORMALIZED_POWER_WINDOW..seconds.len() {
+        window += seconds[i] - seconds[i - NORMALIZED_POWER_WINDOW];
+        total += (window / NORMALIZED_POWER_WINDOW as f64).powi(4);
+    }
+    let count = (seconds.len() - NORMALIZED_POWER_WINDOW + 1) as f64;
+    Some((total / count).powf(0.25))
+}
+
+impl Fit {
+    /// Recomputes the statistics of every `Lap` and `Session` from the `Record` messages
+    /// between its `start_time` and `timestamp`: elapsed, timer and moving time, distance,
+    /// calories, speed, heart rate, cadence, power, ascent/descent and normalized power.
+    ///
+    /// Averages are weighted by the time each record covers and periods where the timer
+    /// was stopped are skipped. Statistics a message lacks are added with the base type of
+    /// their profile field; those the records cannot supply are left as they are.
+    pub fn recompute_summaries(&mut self) {
+        let mut samples: Vec<Sample> = vec![];
+        let mut timer_events: Vec<(u32, bool)> = vec![];
+        for message in &self.data {
+            if let FitMessage::Data(msg) = message {
+                let values = &msg.data.values;
+                match msg.data.message_type {
+                    MessageType::Record => samples.extend(Sample::from_record(values)),
+                    MessageType::Event => {
+                        let timestamp = get_field_time(253, values);
+                        if let (Some(Value::Enum("timer")), Some(Value::Enum(event_type))) =
+                            (get_field_value(0, values), get_field_value(1, values))
+                        {
+                            timer_events.extend(timestamp.map(|t| (t, event_type == "start")));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        samples.sort_by_key(|s| s.time);
+        timer_events.sort_by_key(|(t, _)| *t);
+        // A record interval counts when the last timer event before it was a start.
+        let running: Vec<bool> = samples
+            .windows(2)
+            .map(|pair| {
+                let i = timer_events.partition_point(|(t, _)| *t <= pair[0].time);
+                i == 0 || timer_events[i - 1].1
+            })
+            .collect();
+
+        for message in &mut self.data {
+            let msg = match message {
+                FitMessage::Data(msg) => msg,
+                FitMessage::Definition(_) => continue,
+            };
+            let fields = match msg.data.message_type {
+                MessageType::Lap => LAP_FIELDS,
+                MessageType::Session => SESSION_FIELDS,
+                _ => continue,
+            };
+            let values = &mut msg.data.values;
+            let (start, end) = match (get_field_time(2, values), get_field_time(253, values)) {
+                (Some(start), Some(end)) => (start, end),
+                _ => continue,
+            };
+            let from = samples.partition_point(|s| s.time < start);
+            let to = samples.partition_point(|s| s.time <= end);
+            if to <= from {
+                continue;
+            }
+            let span: Vec<&Sample> = samples[from..to].iter().collect();
+            let summary = Summary::new(&span, &running[from..to - 1]);
+            for (field_num, value) in fields.iter().zip(summary.values()) {
+                if let Some(value) = value {
+                    put_field_scaled(msg.data.message_type, *field_num, value, values);
+                }
+            }
+        }
+        self.define_added_fields();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::{scaled_field, FitBuilder};
+    use crate::protocol::data_field::DataField;
+    use crate::protocol::message_type::MessageType;
+    use crate::protocol::value::Value;
+    use crate::test_util::{push_records, START};
+    use crate::Fit;
+
+    #[test]
+    fn recompute_summaries_test() {
+        let mut builder = FitBuilder::new();
+        builder.push_timer_event(START, "start");
+        // 60 s at 100 W, a 10 s stop, then a single 300 W interval of 10 s.
+        let fields = |t: u32| {
+            let power = match t {
+                0 => 100,
+                80 => 0,
+                _ => 300,
+            };
+            vec![
+                scaled_field(MessageType::Record, 5, t as f64 * 5.0),
+                DataField::new(7, Value::U16(power)),
+            ]
+        };
+        push_records(&mut builder, [0, 60], fields);
+        builder.push_timer_event(START + 60, "stop_all");
+        builder.push_timer_event(START + 70, "start");
+        push_records(&mut builder, [70, 80], fields);
+        let session_fields = [7, 8, 9, 14, 20, 21, 59];
+        let mut session = vec![
+            DataField::new(253, Value::Time(START + 80)),
+            DataField::new(2, Value::Time(START)),
+        ];
+        session.extend(
+            session_fields
+                .iter()
+                .map(|n| scaled_field(MessageType::Session, *n, 0.0)),
+        );
+        builder.push(MessageType::Session, session);
+        // A lap with no statistics at all gets the ones the records supply.
+        builder.push(
+            MessageType::Lap,
+            vec![
+                DataField::new(253, Value::Time(START + 80)),
+                DataField::new(2, Value::Time(START)),
+            ],
+        );
+        let mut fit = builder.build();
+        fit.recompute_summaries();
+        let fit = Fit::read(fit.to_bytes().unwrap()).unwrap();
+        let lap = fit.first(MessageType::Lap).unwrap();
+        assert_eq!(lap.get_f64(7), Some(80.0));
+        assert_eq!(lap.get_f64(9), Some(400.0));
+        assert_eq!(lap.get_f64(19), Some(129.0));
+        assert_eq!(lap.get_f64(20), Some(300.0));
+        assert!(lap.get(33).is_some());
+        assert_eq!(lap.get(15), None);
+        assert_eq!(lap.get(16), None);
+
+        let session = fit.first(MessageType::Session).unwrap();
+        let get = |n| session.get_f64(n).unwrap();
+        assert_eq!(get(7), 80.0);
+        assert_eq!(get(8), 70.0);
+        assert_eq!(get(9), 400.0);
+        assert!((get(14) - 400.0 / 70.0).abs() < 0.001);
+        assert_eq!(get(20), 129.0);
+        assert_eq!(get(21), 300.0);
+        assert_eq!(get(59), 70.0);
+    }
+}
@@ -0,0 +1,48 @@
+use crate::builder::FitBuilder;
+use crate::protocol::data_field::DataField;
+use crate::protocol::message_type::MessageType;
+use crate::protocol::value::Value;
+
+/// Unix time the test recordings start at.
+pub(crate) const START: u32 = 1_000_000_000;
+
+/// A builder holding the `FileId` of an activity file.
+pub(crate) fn activity_builder() -> FitBuilder {
+    let mut builder = FitBuilder::new();
+    builder.push(
+        MessageType::FileId,
+        vec![DataField::new(0, Value::Enum("activity"))],
+    );
+    builder
+}
+
+/// Appends a `Record` at `START + t` for every `t` of `times`, carrying the fields `fields`
+/// gives for it.
+pub(crate) fn push_records<F>(
+    builder: &mut FitBuilder,
+    times: impl IntoIterator<Item = u32>,
+    fields: F,
+) where
+    F: Fn(u32) -> Vec<DataField>,
+{
+    for t in times {
+        let mut values = vec![DataField::new(253, Value::Time(START + t))];
+        values.extend(fields(t));
+        builder.push(MessageType::Record, values);
+    }
+}
+
+/// An activity `FileId` followed by a `Record` for every `t` of `times`.
+pub(crate) fn recording<F>(times: impl IntoIterator<Item = u32>, fields: F) -> FitBuilder
+where
+    F: Fn(u32) -> Vec<DataField>,
+{
+    let mut builder = activity_builder();
+    push_records(&mut builder, times, fields);
+    builder
+}
+
+/// A heart rate of `140 + t` bpm.
+pub(crate) fn heart_rate(t: u32) -> Vec<DataField> {
+    vec![DataField::new(3, Value::U8(140 + t as u8))]
+}
@@ -0,0 +1,318 @@
+use crate::protocol::get_field_name::{get_field_name, get_field_units};
+use crate::protocol::FitDataMessage;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+const METRES_PER_MILE: f64 = 1609.344;
+const SEMICIRCLES_PER_DEGREE: f64 = 2_147_483_648.0 / 180.0;
+
+/// A unit of measure, from those the SDK profile uses to the ones values are shown in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Unit {
+    Metre,
+    Kilometre,
+    Mile,
+    Foot,
+    Millimetre,
+    MetrePerSecond,
+    KilometrePerHour,
+    MilePerHour,
+    /// Pace, in minutes per kilometre.
+    MinPerKm,
+    /// Pace, in minutes per mile.
+    MinPerMile,
+    Degree,
+    Semicircle,
+    Celsius,
+    Fahrenheit,
+    Second,
+    Millisecond,
+    Minute,
+    Hour,
+    Kilocalorie,
+    Kilojoule,
+    Watt,
+    BeatsPerMinute,
+    RevolutionsPerMinute,
+    Percent,
+    Volt,
+    Cycle,
+}
+
+/// What a unit measures; units convert only within a dimension.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Dimension {
+    Length,
+    Speed,
+    Angle,
+    Temperature,
+    Time,
+    Energy,
+    Power,
+    HeartRate,
+    Cadence,
+    Ratio,
+    Voltage,
+    Count,
+}
+
+impl Unit {
+    /// The unit of a `get_field_units` profile unit such as `"m/s"`.
+    pub fn from_profile(units: &str) -> Option<Unit> {
+        let unit = match units {
+            "m" => Unit::Metre,
+            "km" => Unit::Kilometre,
+            "mm" => Unit::Millimetre,
+            "m/s" => Unit::MetrePerSecond,
+            "degrees" => Unit::Degree,
+            "semicircles" => Unit::Semicircle,
+            "C" => Unit::Celsius,
+            "s" => Unit::Second,
+            "ms" => Unit::Millisecond,
+            "kcal" => Unit::Kilocalorie,
+            "watts" => Unit::Watt,
+            "bpm" => Unit::BeatsPerMinute,
+            "rpm" => Unit::RevolutionsPerMinute,
+            "%" => Unit::Percent,
+            "V" => Unit::Volt,
+            "cycles" => Unit::Cycle,
+            _ => return None,
+        };
+        Some(unit)
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Unit::Metre => "m",
+            Unit::Kilometre => "km",
+            Unit::Mile => "mi",
+            Unit::Foot => "ft",
+            Unit::Millimetre => "mm",
+            Unit::MetrePerSecond => "m/s",
+            Unit::KilometrePerHour => "km/h",
+            Unit::MilePerHour => "mph",
+            Unit::MinPerKm => "min/km",
+            Unit::MinPerMile => "min/mi",
+            Unit::Degree => "°",
+            Unit::Semicircle => "semicircles",
+            Unit::Celsius => "°C",
+            Unit::Fahrenheit => "°F",
+            Unit::Second => "s",
+            Unit::Millisecond => "ms",
+            Unit::Minute => "min",
+            Unit::Hour => "h",
+            Unit::Kilocalorie => "kcal",
+            Unit::Kilojoule => "kJ",
+            Unit::Watt => "W",
+            Unit::BeatsPerMinute => "bpm",
+            Unit::RevolutionsPerMinute => "rpm",
+            Unit::Percent => "%",
+            Unit::Volt => "V",
+            Unit::Cycle => "cycles",
+        }
+    }
+
+    fn dimension(self) -> Dimension {
+        match self {
+            Unit::Metre | Unit::Kilometre | Unit::Mile | Unit::Foot | Unit::Millimetre => {
+                Dimension::Length
+            }
+            Unit::MetrePerSecond
+            | Unit::KilometrePerHour
+            | Unit::MilePerHour
+            | Unit::MinPerKm
+            | Unit::MinPerMile => Dimension::Speed,
+            Unit::Degree | Unit::Semicircle => Dimension::Angle,
+            Unit::Celsius | Unit::Fahrenheit => Dimension::Temperature,
+            Unit::Second | Unit::Millisecond | Unit::Minute | Unit::Hour => Dimension::Time,
+            Unit::Kilocalorie | Unit::Kilojoule => Dimension::Energy,
+            Unit::Watt => Dimension::Power,
+            Unit::BeatsPerMinute => Dimension::HeartRate,
+            Unit::RevolutionsPerMinute => Dimension::Cadence,
+            Unit::Percent => Dimension::Ratio,
+            Unit::Volt => Dimension::Voltage,
+            Unit::Cycle => Dimension::Count,
+        }
+    }
+
+    /// Converts `value` to the base unit of its dimension: metres, metres per second,
+    /// degrees, degrees Celsius, seconds or kilocalories.
+    fn base_value(self, value: f64) -> f64 {
+        match self {
+            Unit::Kilometre => value * 1000.0,
+            Unit::Mile => value * METRES_PER_MILE,
+            Unit::Foot => value * 0.3048,
+            Unit::Millimetre => value / 1000.0,
+            Unit::KilometrePerHour => value / 3.6,
+            Unit::MilePerHour => value * METRES_PER_MILE / 3600.0,
+            Unit::MinPerKm => 1000.0 / (value * 60.0),
+            Unit::MinPerMile => METRES_PER_MILE / (value * 60.0),
+            Unit::Semicircle => value / SEMICIRCLES_PER_DEGREE,
+            Unit::Fahrenheit => (value - 32.0) / 1.8,
+            Unit::Millisecond => value / 1000.0,
+            Unit::Minute => value * 60.0,
+            Unit::Hour => value * 3600.0,
+            Unit::Kilojoule => value / 4.184,
+            _ => value,
+        }
+    }
+
+    fn unit_value(self, value: f64) -> f64 {
+        match self {
+            Unit::Kilometre => value / 1000.0,
+            Unit::Mile => value / METRES_PER_MILE,
+            Unit::Foot => value / 0.3048,
+            Unit::Millimetre => value * 1000.0,
+            Unit::KilometrePerHour => value * 3.6,
+            Unit::MilePerHour => value * 3600.0 / METRES_PER_MILE,
+            // Pace is the inverse of speed, so the conversion is its own inverse.
+            Unit::MinPerKm | Unit::MinPerMile => self.base_value(value),
+            Unit::Semicircle => value * SEMICIRCLES_PER_DEGREE,
+            Unit::Fahrenheit => value * 1.8 + 32.0,
+            Unit::Millisecond => value * 1000.0,
+            Unit::Minute => value / 60.0,
+            Unit::Hour => value / 3600.0,
+            Unit::Kilojoule => value * 4.184,
+            _ => value,
+        }
+    }
+}
+
+/// A value with its unit.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Quantity {
+    pub value: f64,
+    pub unit: Unit,
+}
+
+impl Quantity {
+    pub fn new(value: f64, unit: Unit) -> Self {
+        Quantity { value, unit }
+    }
+
+    /// The same quantity in another unit, or `None` if `unit` measures something else.
+    /// A speed of zero is an infinite pace.
+    pub fn to(self, unit: Unit) -> Option<Quantity> {
+        if self.unit.dimension() != unit.dimension() {
+            return None;
+        }
+        if self.unit == unit {
+            return Some(self);
+        }
+        let value = unit.unit_value(self.unit.base_value(self.value));
+        Some(Quantity { value, unit })
+    }
+}
+
+/// Prints the value and unit symbol; paces are printed as minutes and seconds.
+impl Display for Quantity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.unit {
+            Unit::MinPerKm | Unit::MinPerMile if self.value.is_finite() => {
+                let seconds = (self.value * 60.0).round() as u64;
+                write!(
+                    f,
+                    "{}:{:02} {}",
+                    seconds / 60,
+                    seconds % 60,
+                    self.unit.symbol()
+                )
+            }
+            _ => match f.precision() {
+                Some(precision) => {
+                    write!(f, "{:.*} {}", precision, self.value, self.unit.symbol())
+                }
+                None => write!(f, "{} {}", self.value, self.unit.symbol()),
+            },
+        }
+    }
+}
+
+impl FitDataMessage {
+    /// The physical value of a field with its profile unit, for the fields
+    /// `get_field_units` knows.
+    pub fn quantity(&self, field_num: u8) -> Option<Quantity> {
+        let unit = Unit::from_profile(get_field_units(self.data.message_type, field_num)?)?;
+        Some(Quantity::new(self.get_f64(field_num)?, unit))
+    }
+
+    /// The first of the named fields the message has a valid value for.
+    fn named(&self, names: &[&str]) -> Option<Quantity> {
+        names.iter().find_map(|name| {
+            let field = self.data.values.iter().find(|field| {
+                get_field_name(self.data.message_type, field.field_num) == Some(*name)
+            })?;
+            self.quantity(field.field_num)
+        })
+    }
+
+    pub fn speed(&self) -> Option<Quantity> {
+        self.named(&["enhanced_speed", "speed"])
+    }
+
+    pub fn distance(&self) -> Option<Quantity> {
+        self.named(&["distance", "total_distance"])
+    }
+
+    pub fn altitude(&self) -> Option<Quantity> {
+        self.named(&["enhanced_altitude", "altitude"])
+    }
+
+    pub fn heart_rate(&self) -> Option<Quantity> {
+        self.named(&["heart_rate"])
+    }
+
+    pub fn cadence(&self) -> Option<Quantity> {
+        self.named(&["cadence"])
+    }
+
+    pub fn power(&self) -> Option<Quantity> {
+        self.named(&["power"])
+    }
+
+    pub fn temperature(&self) -> Option<Quantity> {
+        self.named(&["temperature"])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::data_field::DataField;
+    use crate::protocol::message_type::MessageType;
+    use crate::protocol::value::Value;
+    use crate::test_util::recording;
+    use crate::units::{Quantity, Unit};
+    use crate::Fit;
+
+    #[test]
+    fn units_test() {
+        let builder = recording([0], |_| {
+            vec![
+                DataField::new(6, Value::U16(4000)),
+                DataField::new(13, Value::I8(20)),
+            ]
+        });
+        let fit = Fit::read(builder.build().to_bytes().unwrap()).unwrap();
+        let record = fit.first(MessageType::Record).unwrap();
+
+        let speed = record.speed().unwrap();
+        assert_eq!(speed, Quantity::new(4.0, Unit::MetrePerSecond));
+        assert_eq!(
+            format!("{:.1}", speed.to(Unit::KilometrePerHour).unwrap()),
+            "14.4 km/h"
+        );
+        assert_eq!(speed.to(Unit::MinPerKm).unwrap().to_string(), "4:10 min/km");
+        assert_eq!(speed.to(Unit::Foot), None);
+        let temperature = record.temperature().unwrap().to(Unit::Fahrenheit).unwrap();
+        assert_eq!(temperature.value, 68.0);
+        assert_eq!(record.power(), None);
+
+        let pace = Quantity::new(8.0, Unit::MinPerMile);
+        let back = pace
+            .to(Unit::MinPerKm)
+            .unwrap()
+            .to(Unit::MinPerMile)
+            .unwrap();
+        assert!((back.value - 8.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,341 @@
+use crate::protocol::base_type::{base_type_name, base_type_size, profile_base_type, ENUM};
+use crate::protocol::get_field_string_value::FieldType;
+use crate::protocol::get_field_type::get_field_type_fn;
+use crate::protocol::macros::{get_field_time, get_field_value};
+use crate::protocol::message_type::MessageType;
+use crate::protocol::value::Value;
+use crate::protocol::FitMessage;
+use crate::Fit;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A problem found by `Fit::validate`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub severity: Severity,
+    /// Position of the offending message in `Fit::data`, `None` for problems with the
+    /// file as a whole.
+    pub index: Option<usize>,
+    pub message: String,
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        match self.index {
+            Some(index) => write!(f, "{} at message {}: {}", severity, index, self.message),
+            None => write!(f, "{}: {}", severity, self.message),
+        }
+    }
+}
+
+/// Messages a file of the given `File` type must contain, and those it should contain.
+fn required_messages(file_type: &str) -> (&'static [MessageType], &'static [MessageType]) {
+    match file_type {
+        "activity" => (
+            &[MessageType::Session, MessageType::Activity],
+            &[MessageType::Lap, MessageType::Record],
+        ),
+        "course" => (
+            &[MessageType::Course, MessageType::Record],
+            &[MessageType::Lap],
+        ),
+        "workout" => (&[MessageType::Workout, MessageType::WorkoutStep], &[]),
+        _ => (&[], &[]),
+    }
+}
+
+impl Fit {
+    /// Checks the file against the FIT file rules and the SDK profile: `FileId` first,
+    /// the messages its `File` type requires, field sizes, timestamp order, `message_index`
+    /// numbering, lap and session references and enum values known to the profile.
+    pub fn validate(&self) -> Vec<Issue> {
+        let mut issues = vec![];
+        let mut issue = |severity, index, message: String| {
+            issues.push(Issue {
+                severity,
+                index,
+                message,
+            })
+        };
+
+        let mut file_type = None;
+        let mut first_data = true;
+        // Keyed by global message number.
+        let mut counts: HashMap<u16, usize> = HashMap::new();
+        let mut indexes: HashMap<u16, u16> = HashMap::new();
+        let mut last_record = None;
+        let mut last_time = None;
+        let mut sessions = vec![];
+        let mut activities = vec![];
+        for (index, message) in self.data.iter().enumerate() {
+            let msg = match message {
+                FitMessage::Data(msg) => msg,
+                FitMessage::Definition(def) => {
+                    let message_type = MessageType::from_primitive(def.data.global_message_number);
+                    let field_types = match message_type {
+                        Some(message_type) => get_field_type_fn(message_type),
+                        None => continue,
+                    };
+                    for field in &def.data.fields {
+                        let declared = field.base_type.val;
+                        let profile =
+                            profile_base_type(field_types(field.definition_number as usize));
+                        if let Some(profile) = profile {
+                            if declared & 0x1F != profile & 0x1F {
+                                issue(
+                                    Severity::Warning,
+                                    Some(index),
+                                    format!(
+                                        "{:?} field {} is declared as {}, the profile expects {}",
+                                        message_type.unwrap_or(MessageType::None),
+                                        field.definition_number,
+                                        base_type_name(declared),
+                                        base_type_name(profile)
+                                    ),
+                                );
+                            }
+                        }
+                        let size = base_type_size(profile.unwrap_or(declared));
+                        if field.size == 0 || field.size % size != 0 {
+                            issue(
+                                Severity::Error,
+                                Some(index),
+                                format!(
+                                    "{:?} field {} has size {}, expected a multiple of {}",
+                                    message_type.unwrap_or(MessageType::None),
+                                    field.definition_number,
+                                    field.size,
+                                    size
+                                ),
+                            );
+                        }
+                    }
+                    continue;
+                }
+            };
+            let message_type = msg.data.message_type;
+            let values = &msg.data.values;
+            if first_data {
+                first_data = false;
+                if message_type != MessageType::FileId {
+                    issue(
+                        Severity::Error,
+                        Some(index),
+                        format!("file starts with {:?} instead of FileId", message_type),
+                    );
+                }
+            }
+            *counts.entry(message_type as u16).or_default() += 1;
+            match message_type {
+                MessageType::FileId if file_type.is_none() => {
+                    file_type = match get_field_value(0, values) {
+                        Some(Value::Enum(file_type)) => Some(file_type),
+                        _ => Some(""),
+                    };
+                }
+                MessageType::Session => sessions.push((index, values)),
+                MessageType::Activity => activities.push((index, values)),
+                _ => {}
+            }
+
+            if let Some(time) = get_field_time(253, values) {
+                if message_type == MessageType::Record {
+                    if last_record.is_some_and(|last| time < last) {
+                        issue(
+                            Severity::Error,
+                            Some(index),
+                            "record timestamp goes backwards".to_string(),
+                        );
+                    }
+                    last_record = Some(time);
+                } else if last_time.is_some_and(|last| time < last) {
+                    issue(
+                        Severity::Warning,
+                        Some(index),
+                        format!(
+                            "{:?} timestamp is earlier than the message before",
+                            message_type
+                        ),
+                    );
+                }
+                last_time = last_time.max(Some(time));
+            }
+
+            if let Some(Value::U16(message_index)) = get_field_value(254, values) {
+                let expected = indexes.entry(message_type as u16).or_default();
+                if message_index & 0x0FFF != *expected {
+                    issue(
+                        Severity::Warning,
+                        Some(index),
+                        format!(
+                            "{:?} message_index is {}, expected {}",
+                            message_type,
+                            message_index & 0x0FFF,
+                            expected
+                        ),
+                    );
+                }
+                *expected = (message_index & 0x0FFF) + 1;
+            }
+
+            let field_types = get_field_type_fn(message_type);
+            for field in values {
+                let field_type = field_types(field.field_num as usize);
+                let unknown = match field.value {
+                    Value::U8(v) => v != u8::MAX && profile_base_type(field_type) == Some(ENUM),
+                    Value::U16(v) => v != u16::MAX && field_type == FieldType::Manufacturer,
+                    _ => false,
+                };
+                if unknown {
+                    issue(
+                        Severity::Warning,
+                        Some(index),
+                        format!(
+                            "{:?} field {} has a value unknown to {:?}",
+                            message_type, field.field_num, field_type
+                        ),
+                    );
+                }
+            }
+        }
+
+        match file_type {
+            None => issue(Severity::Error, None, "file has no FileId".to_string()),
+            Some(file_type) => {
+                let (required, recommended) = required_messages(file_type);
+                for (severity, message_types) in [
+                    (Severity::Error, required),
+                    (Severity::Warning, recommended),
+                ] {
+                    for message_type in message_types {
+                        if !counts.contains_key(&(*message_type as u16)) {
+                            issue(
+                                severity,
+                                None,
+                                format!("{} file has no {:?} message", file_type, message_type),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        let laps = counts
+            .get(&(MessageType::Lap as u16))
+            .copied()
+            .unwrap_or_default();
+        for (index, values) in &sessions {
+            if let (Some(Value::U16(first)), Some(Value::U16(count))) =
+                (get_field_value(25, values), get_field_value(26, values))
+            {
+                if first as usize + count as usize > laps {
+                    issue(
+                        Severity::Error,
+                        Some(*index),
+                        format!(
+                            "session refers to laps {}..{} but the file has {}",
+                            first,
+                            first as usize + count as usize,
+                            laps
+                        ),
+                    );
+                }
+            }
+        }
+        for (index, values) in &activities {
+            if let Some(Value::U16(num_sessions)) = get_field_value(1, values) {
+                if num_sessions as usize != sessions.len() {
+                    issue(
+                        Severity::Warning,
+                        Some(*index),
+                        format!(
+                            "activity counts {} sessions but the file has {}",
+                            num_sessions,
+                            sessions.len()
+                        ),
+                    );
+                }
+            }
+        }
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::protocol::base_type::SINT32;
+    use crate::protocol::data_field::DataField;
+    use crate::protocol::message_type::MessageType;
+    use crate::protocol::value::Value;
+    use crate::test_util::recording;
+    use crate::validate::Severity;
+    use crate::{Fit, FitMessage};
+
+    #[test]
+    fn validate_test() {
+        let mut builder = recording([10, 5], |_| vec![]);
+        builder.push(
+            MessageType::Lap,
+            vec![
+                DataField::new(24, Value::U8(200)),
+                DataField::new(254, Value::U16(1)),
+            ],
+        );
+        let mut fit = Fit::read(builder.build().to_bytes().unwrap()).unwrap();
+        if let FitMessage::Definition(def) = &mut fit.data[2] {
+            def.data.fields[0].base_type.val = SINT32;
+        }
+
+        let issues: Vec<_> = fit
+            .validate()
+            .into_iter()
+            .map(|issue| (issue.severity, issue.index, issue.message))
+            .collect();
+        let expected = [
+            (
+                Severity::Warning,
+                Some(2),
+                "Record field 253 is declared as sint32, the profile expects uint32",
+            ),
+            (Severity::Error, Some(4), "record timestamp goes backwards"),
+            (
+                Severity::Warning,
+                Some(6),
+                "Lap message_index is 1, expected 0",
+            ),
+            (
+                Severity::Warning,
+                Some(6),
+                "Lap field 24 has a value unknown to LapTrigger",
+            ),
+            (
+                Severity::Error,
+                None,
+                "activity file has no Session message",
+            ),
+            (
+                Severity::Error,
+                None,
+                "activity file has no Activity message",
+            ),
+        ];
+        assert_eq!(
+            issues,
+            expected
+                .iter()
+                .map(|&(severity, index, message)| (severity, index, message.to_string()))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,458 @@
+use crate::builder::FitBuilder;
+use crate::protocol::data_field::DataField;
+use crate::protocol::io::invalid_data;
+use crate::protocol::macros::get_field_value;
+use crate::protocol::message_type::MessageType;
+use crate::protocol::value::Value;
+use crate::protocol::FitMessage;
+use crate::Fit;
+use binrw::BinResult;
+use std::path::Path;
+
+// Heart rate and power durations/targets below these offsets are percentages of max HR / FTP.
+const HEART_RATE_OFFSET: u32 = 100;
+const POWER_OFFSET: u32 = 1000;
+
+/// A structured workout as stored in a FIT workout file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Workout {
+    pub name: Option<String>,
+    /// A `Sport` profile value such as `"cycling"`.
+    pub sport: &'static str,
+    pub steps: Vec<WorkoutStep>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkoutStep {
+    Step(Step),
+    /// Repeats the nested steps `count` times.
+    Repeat {
+        count: u32,
+        steps: Vec<WorkoutStep>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub name: Option<String>,
+    pub duration: StepDuration,
+    pub target: StepTarget,
+    pub intensity: Intensity,
+    pub notes: Option<String>,
+}
+
+/// When a step ends. Time is in seconds, distance in metres, heart rate in bpm and power in
+/// watts.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum StepDuration {
+    Open,
+    Time(f64),
+    Distance(f64),
+    Calories(u32),
+    HeartRateLessThan(u32),
+    HeartRateGreaterThan(u32),
+    PowerLessThan(u32),
+    PowerGreaterThan(u32),
+}
+
+/// What the athlete aims for during a step. Zones are 1 based, custom ranges use bpm, watts,
+/// m/s and rpm.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum StepTarget {
+    Open,
+    HeartRateZone(u32),
+    HeartRate { low: u32, high: u32 },
+    PowerZone(u32),
+    Power { low: u32, high: u32 },
+    SpeedZone(u32),
+    Speed { low: f64, high: f64 },
+    Cadence { low: u32, high: u32 },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Intensity {
+    Active,
+    Rest,
+    Warmup,
+    Cooldown,
+    Recovery,
+    Interval,
+    Other,
+}
+
+impl Intensity {
+    fn to_profile(self) -> &'static str {
+        match self {
+            Intensity::Active => "active",
+            Intensity::Rest => "rest",
+            Intensity::Warmup => "warmup",
+            Intensity::Cooldown => "cooldown",
+            Intensity::Recovery => "recovery",
+            Intensity::Interval => "interval",
+            Intensity::Other => "other",
+        }
+    }
+
+    fn from_profile(s: &str) -> Self {
+        match s {
+            "rest" => Intensity::Rest,
+            "warmup" => Intensity::Warmup,
+            "cooldown" => Intensity::Cooldown,
+            "recovery" => Intensity::Recovery,
+            "interval" => Intensity::Interval,
+            "other" => Intensity::Other,
+            _ => Intensity::Active,
+        }
+    }
+}
+
+impl Workout {
+    /// Encodes the workout as a FIT workout file: `FileId`, `Workout` and one `WorkoutStep`
+    /// per step, with repeat blocks flattened into `repeat_until_steps_cmplt` steps.
+    ///
+    /// Fails when a heart rate or power value is too large to encode.
+    pub fn to_fit(&self) -> BinResult<Fit> {
+        let mut steps = vec![];
+        flatten_steps(&self.steps, &mut steps)?;
+
+        let mut builder = FitBuilder::new();
+        builder.push(
+            MessageType::FileId,
+            vec![
+                DataField::new(0, Value::Enum("workout")),
+                DataField::new(1, Value::Enum("development")),
+                DataField::new(2, Value::U16(0)),
+            ],
+        );
+        let mut workout = vec![
+            DataField::new(4, Value::Enum(self.sport)),
+            DataField::new(6, Value::U16(steps.len() as u16)),
+        ];
+        if let Some(name) = &self.name {
+            workout.push(DataField::new(8, Value::String(name.clone())));
+        }
+        builder.push(MessageType::Workout, workout);
+        for step in steps {
+            builder.push(MessageType::WorkoutStep, step);
+        }
+        Ok(builder.build())
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> BinResult<()> {
+        self.to_fit()?.write(path)
+    }
+
+    /// Reads the `Workout` and `WorkoutStep` messages of a workout file back into a `Workout`,
+    /// nesting steps covered by `repeat_until_steps_cmplt` into repeat blocks.
+    pub fn from_fit(fit: &Fit) -> BinResult<Workout> {
+        let mut workout = None;
+        let mut messages = vec![];
+        for message in &fit.data {
+            if let FitMessage::Data(msg) = message {
+                match msg.data.message_type {
+                    MessageType::Workout => workout = Some(&msg.data.values),
+                    MessageType::WorkoutStep => messages.push(&msg.data.values),
+                    _ => {}
+                }
+            }
+        }
+        let workout = workout.ok_or_else(|| invalid_data("file has no workout message"))?;
+        messages.sort_by_key(|values| get_u32(254, values));
+
+        // (index of the first flattened step covered, step)
+        let mut steps: Vec<(u32, WorkoutStep)> = vec![];
+        for (index, values) in messages.iter().enumerate() {
+            let index = get_u32(254, values).unwrap_or(index as u32);
+            let duration_value = get_u32(2, values);
+            match get_field_value(1, values) {
+                Some(Value::Enum("repeat_until_steps_cmplt")) => {
+                    let from = duration_value.unwrap_or_default();
+                    let split = steps.partition_point(|(start, _)| *start < from);
+                    let nested = steps.split_off(split).into_iter().map(|(_, s)| s).collect();
+                    let count = get_u32(4, values).unwrap_or(1);
+                    steps.push((
+                        from.min(index),
+                        WorkoutStep::Repeat {
+                            count,
+                            steps: nested,
+                        },
+                    ));
+                }
+                duration_type => {
+                    let step = Step {
+                        name: get_string(0, values),
+                        duration: parse_duration(duration_type, duration_value),
+                        target: parse_target(values),
+                        intensity: match get_field_value(7, values) {
+                            Some(Value::Enum(s)) => Intensity::from_profile(s),
+                            _ => Intensity::Active,
+                        },
+                        notes: get_string(8, values),
+                    };
+                    steps.push((index, WorkoutStep::Step(step)));
+                }
+            }
+        }
+
+        Ok(Workout {
+            name: get_string(8, workout),
+            sport: match get_field_value(4, workout) {
+                Some(Value::Enum(sport)) => sport,
+                _ => "generic",
+            },
+            steps: steps.into_iter().map(|(_, step)| step).collect(),
+        })
+    }
+}
+
+fn flatten_steps(steps: &[WorkoutStep], out: &mut Vec<Vec<DataField>>) -> BinResult<()> {
+    for step in steps {
+        match step {
+            WorkoutStep::Step(step) => {
+                let index = out.len() as u16;
+                out.push(step_fields(index, step)?);
+            }
+            WorkoutStep::Repeat { count, steps } => {
+                let from = out.len() as u32;
+                flatten_steps(steps, out)?;
+                let index = out.len() as u16;
+                out.push(vec![
+                    DataField::new(254, Value::U16(index)),
+                    DataField::new(1, Value::Enum("repeat_until_steps_cmplt")),
+                    DataField::new(2, Value::U32(from)),
+                    DataField::new(3, Value::Enum("open")),
+                    DataField::new(4, Value::U32(*count)),
+                ]);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Adds the offset that tells heart rate and power values apart from zone numbers.
+fn with_offset(value: u32, offset: u32) -> BinResult<u32> {
+    value
+        .checked_add(offset)
+        .ok_or_else(|| invalid_data(format!("workout step value {} is too large", value)))
+}
+
+fn step_fields(index: u16, step: &Step) -> BinResult<Vec<DataField>> {
+    let mut fields = vec![DataField::new(254, Value::U16(index))];
+    if let Some(name) = &step.name {
+        fields.push(DataField::new(0, Value::String(name.clone())));
+    }
+    let (duration_type, duration_value) = match step.duration {
+        StepDuration::Open => ("open", None),
+        StepDuration::Time(secs) => ("time", Some((secs * 1000.0).round() as u32)),
+        StepDuration::Distance(m) => ("distance", Some((m * 100.0).round() as u32)),
+        StepDuration::Calories(kcal) => ("calories", Some(kcal)),
+        StepDuration::HeartRateLessThan(bpm) => {
+            ("hr_less_than", Some(with_offset(bpm, HEART_RATE_OFFSET)?))
+        }
+        StepDuration::HeartRateGreaterThan(bpm) => (
+            "hr_greater_than",
+            Some(with_offset(bpm, HEART_RATE_OFFSET)?),
+        ),
+        StepDuration::PowerLessThan(watts) => {
+            ("power_less_than", Some(with_offset(watts, POWER_OFFSET)?))
+        }
+        StepDuration::PowerGreaterThan(watts) => (
+            "power_greater_than",
+            Some(with_offset(watts, POWER_OFFSET)?),
+        ),
+    };
+    fields.push(DataField::new(1, Value::Enum(duration_type)));
+    if let Some(value) = duration_value {
+        fields.push(DataField::new(2, Value::U32(value)));
+    }
+
+    let (target_type, target_value, range) = match step.target {
+        StepTarget::Open => ("open", 0, None),
+        StepTarget::HeartRateZone(zone) => ("heart_rate", zone, None),
+        StepTarget::HeartRate { low, high } => (
+            "heart_rate",
+            0,
+            Some((
+                with_offset(low, HEART_RATE_OFFSET)?,
+                with_offset(high, HEART_RATE_OFFSET)?,
+            )),
+        ),
+        StepTarget::PowerZone(zone) => ("power", zone, None),
+        StepTarget::Power { low, high } => {
+            let range = (
+                with_offset(low, POWER_OFFSET)?,
+                with_offset(high, POWER_OFFSET)?,
+            );
+            ("power", 0, Some(range))
+        }
+        StepTarget::SpeedZone(zone) => ("speed", zone, None),
+        StepTarget::Speed { low, high } => (
+            "speed",
+            0,
+            Some((
+                (low * 1000.0).round() as u32,
+                (high * 1000.0).round() as u32,
+            )),
+        ),
+        StepTarget::Cadence { low, high } => ("cadence", 0, Some((low, high))),
+    };
+    fields.push(DataField::new(3, Value::Enum(target_type)));
+    fields.push(DataField::new(4, Value::U32(target_value)));
+    if let Some((low, high)) = range {
+        fields.push(DataField::new(5, Value::U32(low)));
+        fields.push(DataField::new(6, Value::U32(high)));
+    }
+    fields.push(DataField::new(7, Value::Enum(step.intensity.to_profile())));
+    if let Some(notes) = &step.notes {
+        fields.push(DataField::new(8, Value::String(notes.clone())));
+    }
+    Ok(fields)
+}
+
+fn parse_duration(duration_type: Option<Value>, value: Option<u32>) -> StepDuration {
+    let value = match value {
+        Some(value) => value,
+        None => return StepDuration::Open,
+    };
+    match duration_type {
+        Some(Value::Enum("time")) => StepDuration::Time(value as f64 / 1000.0),
+        Some(Value::Enum("distance")) => StepDuration::Distance(value as f64 / 100.0),
+        Some(Value::Enum("calories")) => StepDuration::Calories(value),
+        Some(Value::Enum("hr_less_than")) => {
+            StepDuration::HeartRateLessThan(value.saturating_sub(HEART_RATE_OFFSET))
+        }
+        Some(Value::Enum("hr_greater_than")) => {
+            StepDuration::HeartRateGreaterThan(value.saturating_sub(HEART_RATE_OFFSET))
+        }
+        Some(Value::Enum("power_less_than")) => {
+            StepDuration::PowerLessThan(value.saturating_sub(POWER_OFFSET))
+        }
+        Some(Value::Enum("power_greater_than")) => {
+            StepDuration::PowerGreaterThan(value.saturating_sub(POWER_OFFSET))
+        }
+        _ => StepDuration::Open,
+    }
+}
+
+fn parse_target(values: &[DataField]) -> StepTarget {
+    let zone = get_u32(4, values).unwrap_or_default();
+    let range = get_u32(5, values).zip(get_u32(6, values));
+    match (get_field_value(3, values), range) {
+        (Some(Value::Enum("heart_rate")), Some((low, high))) if zone == 0 => {
+            StepTarget::HeartRate {
+                low: low.saturating_sub(HEART_RATE_OFFSET),
+                high: high.saturating_sub(HEART_RATE_OFFSET),
+            }
+        }
+        (Some(Value::Enum("heart_rate")), _) => StepTarget::HeartRateZone(zone),
+        (Some(Value::Enum("power")), Some((low, high))) if zone == 0 => StepTarget::Power {
+            low: low.saturating_sub(POWER_OFFSET),
+            high: high.saturating_sub(POWER_OFFSET),
+        },
+        (Some(Value::Enum("power")), _) => StepTarget::PowerZone(zone),
+        (Some(Value::Enum("speed")), Some((low, high))) if zone == 0 => StepTarget::Speed {
+            low: low as f64 / 1000.0,
+            high: high as f64 / 1000.0,
+        },
+        (Some(Value::Enum("speed")), _) => StepTarget::SpeedZone(zone),
+        (Some(Value::Enum("cadence")), Some((low, high))) => StepTarget::Cadence { low, high },
+        _ => StepTarget::Open,
+    }
+}
+
+fn get_u32(field_num: u8, values: &[DataField]) -> Option<u32> {
+    match get_field_value(field_num, values)? {
+        Value::U8(v) if v != u8::MAX => Some(v as u32),
+        Value::U16(v) if v != u16::MAX => Some(v as u32),
+        Value::U32(v) if v != u32::MAX => Some(v),
+        _ => None,
+    }
+}
+
+fn get_string(field_num: u8, values: &[DataField]) -> Option<String> {
+    match get_field_value(field_num, values)? {
+        Value::String(s) => {
+            let s = s.trim_end_matches('\0');
+            (!s.is_empty()).then(|| s.to_string())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::workout::{Intensity, Step, StepDuration, StepTarget, Workout, WorkoutStep};
+    use crate::Fit;
+
+    fn step(duration: StepDuration, target: StepTarget, intensity: Intensity) -> WorkoutStep {
+        WorkoutStep::Step(Step {
+            name: None,
+            duration,
+            target,
+            intensity,
+            notes: None,
+        })
+    }
+
+    #[test]
+    fn workout_round_trip_test() {
+        let workout = Workout {
+            name: Some("4x8min threshold".to_string()),
+            sport: "cycling",
+            steps: vec![
+                step(
+                    StepDuration::Time(600.0),
+                    StepTarget::PowerZone(2),
+                    Intensity::Warmup,
+                ),
+                WorkoutStep::Repeat {
+                    count: 4,
+                    steps: vec![
+                        step(
+                            StepDuration::Time(480.0),
+                            StepTarget::Power {
+                                low: 250,
+                                high: 270,
+                            },
+                            Intensity::Interval,
+                        ),
+                        step(
+                            StepDuration::HeartRateLessThan(120),
+                            StepTarget::Open,
+                            Intensity::Recovery,
+                        ),
+                    ],
+                },
+                WorkoutStep::Step(Step {
+                    name: Some("Easy spin".to_string()),
+                    duration: StepDuration::Open,
+                    target: StepTarget::Cadence { low: 85, high: 95 },
+                    intensity: Intensity::Cooldown,
+                    notes: Some("Lap when done".to_string()),
+                }),
+            ],
+        };
+        let fit = Fit::read(workout.to_fit().unwrap().to_bytes().unwrap()).unwrap();
+        assert_eq!(Workout::from_fit(&fit).unwrap(), workout);
+
+        for (duration, target) in [
+            (
+                StepDuration::HeartRateGreaterThan(u32::MAX),
+                StepTarget::Open,
+            ),
+            (
+                StepDuration::Open,
+                StepTarget::Power {
+                    low: 100,
+                    high: u32::MAX,
+                },
+            ),
+        ] {
+            let workout = Workout {
+                steps: vec![step(duration, target, Intensity::Active)],
+                ..workout.clone()
+            };
+            assert!(workout.to_fit().is_err());
+        }
+    }
+}